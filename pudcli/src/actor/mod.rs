@@ -23,12 +23,14 @@ use bincode::{deserialize, serialize};
 use bytes::{Bytes, BytesMut};
 use futures::stream::SplitSink;
 use pudlib::{
-    parse_ts_ping, send_ts_ping, ManagerClientToManagerSession, Schedule, ServerToManagerClient,
+    parse_calendar_seeded, parse_cron, parse_ts_ping, send_ts_ping, Cron, JobEvent,
+    ManagerClientToManagerSession, Realtime, Schedule, ServerToManagerClient,
 };
 use std::{
     collections::VecDeque,
     time::{Duration, Instant},
 };
+use time::OffsetDateTime;
 use tokio::sync::mpsc::UnboundedSender;
 use tracing::{debug, error, info};
 use typed_builder::TypedBuilder;
@@ -62,6 +64,12 @@ pub(crate) struct CommandLine {
     // Current futures handles
     #[builder(default = Vec::new())]
     fut_handles: Vec<SpawnHandle>,
+    // The code to exit with once the actor has stopped
+    #[builder(default = 0)]
+    exit_code: i32,
+    // The status of a job run on request, once it has exited
+    #[builder(default = None)]
+    job_status: Option<i32>,
 }
 
 impl CommandLine {
@@ -151,7 +159,11 @@ impl CommandLine {
                         }
                         ctx.stop();
                     }
-                    ServerToManagerClient::Schedules { name, schedules } => {
+                    ServerToManagerClient::Schedules {
+                        name,
+                        schedules,
+                        timezone,
+                    } => {
                         error!("Retrieved {} schedules from '{name}'", schedules.len());
 
                         for schedule in &schedules {
@@ -160,6 +172,7 @@ impl CommandLine {
                                     on_boot_sec,
                                     on_unit_active_sec,
                                     cmds,
+                                    overlap,
                                 } => {
                                     error!("monotonic:");
                                     error!(
@@ -170,6 +183,7 @@ impl CommandLine {
                                         "     on_unit_active_sec: {}",
                                         on_unit_active_sec.as_secs_f64()
                                     );
+                                    error!("     overlap:            {overlap:?}");
                                     for cmd in cmds {
                                         error!("     cmd:                {cmd}");
                                     }
@@ -178,10 +192,43 @@ impl CommandLine {
                                     on_calendar,
                                     persistent,
                                     cmds,
+                                    overlap,
                                 } => {
                                     error!("realtime:");
                                     error!("     on_calendar: {on_calendar}");
                                     error!("     persistent:  {persistent}");
+                                    error!("     overlap:     {overlap:?}");
+                                    if let Some(next) = parse_calendar_seeded(on_calendar, &name)
+                                        .ok()
+                                        .and_then(|rt| next_run(rt, timezone.as_deref()))
+                                    {
+                                        error!("     next run:    {next}");
+                                    }
+                                    for cmd in cmds {
+                                        error!("     cmd:         {cmd}");
+                                    }
+                                }
+                                Schedule::Cron {
+                                    expr,
+                                    cmds,
+                                    overlap,
+                                } => {
+                                    error!("cron:");
+                                    error!("     expr:        {expr}");
+                                    error!("     overlap:     {overlap:?}");
+                                    match parse_cron(expr) {
+                                        Ok(Cron::Reboot) => {
+                                            error!("     next run:    at worker start");
+                                        }
+                                        Ok(Cron::Calendar(realtime)) => {
+                                            if let Some(next) =
+                                                next_run(*realtime, timezone.as_deref())
+                                            {
+                                                error!("     next run:    {next}");
+                                            }
+                                        }
+                                        Err(_) => {}
+                                    }
                                     for cmd in cmds {
                                         error!("     cmd:         {cmd}");
                                     }
@@ -191,9 +238,12 @@ impl CommandLine {
                         ctx.stop();
                     }
                     ServerToManagerClient::QueryReturn {
-                        stdout,
-                        stderr,
+                        output,
+                        truncated,
+                        stdout_dropped,
+                        stderr_dropped,
                         status,
+                        exit,
                         start_time,
                         end_time,
                         done,
@@ -208,23 +258,91 @@ impl CommandLine {
                         }
                         error!("");
                         error!("STATUS");
-                        error!("     {status}");
-                        error!("");
-                        error!("STDOUT");
-                        for line in &stdout {
-                            error!("     {line}");
+                        if let Some(status) = status {
+                            error!("     {status}");
+                        } else {
+                            error!("     unknown, the job never reported an exit");
+                        }
+                        if let Some(exit) = exit {
+                            error!("     {exit}");
                         }
                         error!("");
-                        error!("STDERR");
-                        for line in &stderr {
+                        error!("OUTPUT");
+                        for line in &output {
                             error!("     {line}");
                         }
+                        if truncated {
+                            error!(
+                                "     ... {stdout_dropped} stdout and {stderr_dropped} stderr line(s) truncated ..."
+                            );
+                        }
                         error!("");
 
                         if done {
                             ctx.stop();
                         }
                     }
+                    ServerToManagerClient::Running(running) => {
+                        error!("{} job(s) running", running.len());
+                        for job in &running {
+                            error!("{} - {} ({})", job.worker(), job.name(), job.id());
+                            error!("     started at:   {}", job.start_time());
+                            error!("     elapsed:      {}s", job.elapsed().as_secs());
+                            error!(
+                                "     output lines: {} stdout, {} stderr",
+                                job.stdout_lines(),
+                                job.stderr_lines()
+                            );
+                        }
+                        ctx.stop();
+                    }
+                    ServerToManagerClient::CancelResult {
+                        worker,
+                        id,
+                        cancelled,
+                    } => {
+                        if cancelled {
+                            error!("job {id} on '{worker}' has been cancelled");
+                        } else {
+                            error!("job {id} is not running on '{worker}'");
+                            self.exit_code = 1;
+                        }
+                        ctx.stop();
+                    }
+                    ServerToManagerClient::RunFailed(reason) => {
+                        error!("unable to run: {reason}");
+                        self.exit_code = 1;
+                        ctx.stop();
+                    }
+                    ServerToManagerClient::JobEvent {
+                        worker,
+                        id,
+                        name,
+                        event,
+                    } if self.tailing() => match event {
+                        JobEvent::Started(_) => error!("[{name}] started on '{worker}' ({id})"),
+                        JobEvent::Output(line) => error!("[{name}] {line}"),
+                        JobEvent::Exit(exit) => error!("[{name}] {exit}"),
+                        JobEvent::Ended => error!("[{name}] ended"),
+                    },
+                    ServerToManagerClient::JobEvent {
+                        worker,
+                        id: _,
+                        name,
+                        event,
+                    } => match event {
+                        JobEvent::Started(_) => error!("'{name}' started on '{worker}'"),
+                        JobEvent::Output(line) => error!("{line}"),
+                        JobEvent::Exit(exit) => {
+                            error!("'{name}' {exit}");
+                            self.job_status = Some(exit.status());
+                        }
+                        JobEvent::Ended => {
+                            // a job that never exited, i.e. failed to spawn, is a failure
+                            self.exit_code = self.job_status.unwrap_or(1);
+                            ctx.stop();
+                        }
+                    },
                 }
             }
             Err(e) => {
@@ -234,6 +352,14 @@ impl CommandLine {
         }
     }
 
+    // Is this command line following output rather than waiting on a result
+    fn tailing(&self) -> bool {
+        matches!(
+            self.command_to_run,
+            ManagerClientToManagerSession::Tail { .. }
+        )
+    }
+
     fn handle_ping(&mut self, bytes: Bytes) {
         debug!("handling ping message");
         if let Some(dur) = parse_ts_ping(&bytes) {
@@ -281,6 +407,16 @@ impl CommandLine {
     }
 }
 
+// The next time a schedule runs, evaluated in the worker's timezone when the
+// calendar does not name one, as the worker does
+fn next_run(realtime: Realtime, timezone: Option<&str>) -> Option<OffsetDateTime> {
+    let realtime = match timezone {
+        Some(timezone) => realtime.with_default_timezone(timezone),
+        None => realtime,
+    };
+    realtime.next_after(OffsetDateTime::now_utc())
+}
+
 impl Actor for CommandLine {
     type Context = Context<Self>;
 
@@ -301,7 +437,7 @@ impl Actor for CommandLine {
     fn stopped(&mut self, _: &mut Self::Context) {
         info!("command line actor stopped");
         // Stop application on disconnect
-        System::current().stop();
+        System::current().stop_with_code(self.exit_code);
     }
 }
 
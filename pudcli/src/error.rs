@@ -15,6 +15,10 @@ use tracing::error;
 pub(crate) enum Error {
     #[error(transparent)]
     Anyhow(#[from] anyhow::Error),
+    #[error("the job exited with status {status}")]
+    JobStatus { status: i32 },
+    #[error("--{arg} reaches back further than a job could have run")]
+    AgeOutOfRange { arg: &'static str },
 }
 
 #[allow(clippy::needless_pass_by_value)]
@@ -23,6 +27,9 @@ pub(crate) fn clap_or_error(err: anyhow::Error) -> i32 {
         eprint!("{err:?}");
         1
     };
+    if let Some(Error::JobStatus { status }) = err.downcast_ref::<Error>() {
+        return *status;
+    }
     match err.downcast_ref::<clap::Error>() {
         Some(e) => match e.kind() {
             ErrorKind::DisplayHelp => {
@@ -60,7 +67,7 @@ pub(crate) fn success((): ()) -> i32 {
 
 #[cfg(test)]
 mod test {
-    use super::{clap_or_error, success};
+    use super::{clap_or_error, success, Error as PudcliError};
     use anyhow::{anyhow, Error};
     use clap::{
         error::ErrorKind::{self, DisplayHelp, DisplayVersion},
@@ -77,6 +84,14 @@ mod test {
         assert_eq!(1, clap_or_error(anyhow!("test")));
     }
 
+    #[test]
+    fn clap_or_error_is_job_status() {
+        assert_eq!(
+            3,
+            clap_or_error(PudcliError::JobStatus { status: 3 }.into())
+        );
+    }
+
     #[test]
     fn clap_or_error_is_help() {
         let mut cmd = Command::new(env!("CARGO_PKG_NAME"));
@@ -10,6 +10,8 @@
 
 use clap::{ArgAction::Count, Parser, Subcommand};
 use getset::Getters;
+use time::Duration;
+use uuid::Uuid;
 
 const CONFIG_FILE_PATH: &str = "config_file_path";
 
@@ -60,7 +62,11 @@ pub(crate) enum Subcommands {
     Reload,
     ListWorkers,
     Schedules(Schedule),
-    Query(Query),
+    History(History),
+    Run(Run),
+    Tail(Tail),
+    Running,
+    Cancel(Cancel),
 }
 
 #[derive(Clone, Debug, Getters, Parser)]
@@ -71,15 +77,90 @@ pub(crate) struct Schedule {
 
 #[derive(Clone, Debug, Getters, Parser)]
 #[getset(get = "pub(crate)")]
-pub(crate) struct Query {
-    query: String,
+pub(crate) struct History {
+    /// The name of the worker the jobs ran on
+    #[arg(long)]
+    worker: String,
+    /// Only show jobs for this command
+    #[arg(long)]
+    cmd: Option<String>,
+    /// Only show jobs started within this long ago, i.e. 30m, 12h, 2d
+    #[arg(long, value_parser = parse_age)]
+    since: Option<Duration>,
+    /// Only show jobs started more than this long ago, i.e. 30m, 12h, 2d
+    #[arg(long, value_parser = parse_age)]
+    until: Option<Duration>,
+    /// Only show jobs that failed
+    #[arg(long, conflicts_with = "succeeded")]
+    failed: bool,
+    /// Only show jobs that succeeded
+    #[arg(long)]
+    succeeded: bool,
+    /// Show at most this many jobs
+    #[arg(long)]
+    limit: Option<usize>,
+    /// Show the oldest jobs first, rather than the newest
+    #[arg(long)]
+    oldest_first: bool,
+}
+
+// Parse an age given as a number and a unit (s, m, h, d or w)
+fn parse_age(age: &str) -> Result<Duration, String> {
+    let invalid = || format!("'{age}' is not an age like 30m, 12h or 2d");
+    let split = age.len() - age.chars().last().map_or(0, char::len_utf8);
+    let (count, unit) = age.split_at(split);
+    let count = count
+        .parse::<i64>()
+        .ok()
+        .filter(|count| *count >= 0)
+        .ok_or_else(invalid)?;
+    let unit_secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    count
+        .checked_mul(unit_secs)
+        .map(Duration::seconds)
+        .ok_or_else(|| format!("'{age}' is too long an age"))
+}
+
+#[derive(Clone, Debug, Getters, Parser)]
+#[getset(get = "pub(crate)")]
+pub(crate) struct Run {
+    /// The name of the worker to run the command on
+    worker: String,
+    /// The name of the command to run
+    command: String,
+}
+
+#[derive(Clone, Debug, Getters, Parser)]
+#[getset(get = "pub(crate)")]
+pub(crate) struct Tail {
+    /// The name of the worker to follow
+    worker: String,
+    /// The job name to follow, all jobs are followed when not given
+    job: Option<String>,
+}
+
+#[derive(Clone, Debug, Getters, Parser)]
+#[getset(get = "pub(crate)")]
+pub(crate) struct Cancel {
+    /// The name of the worker the job is running on
+    worker: String,
+    /// The id of the job to cancel
+    id: Uuid,
 }
 
 #[cfg(test)]
 mod test {
-    use super::Cli;
+    use super::{parse_age, Cli, Subcommands};
     use anyhow::{anyhow, Result};
     use clap::{error::ErrorKind, CommandFactory, Parser};
+    use time::Duration;
 
     #[test]
     fn verify_app() {
@@ -130,6 +211,114 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn run_works() -> Result<()> {
+        let args = Cli::try_parse_from([env!("CARGO_PKG_NAME"), "run", "worker-1", "backup"])?;
+        match args.sub_cmd() {
+            Subcommands::Run(run) => {
+                assert_eq!(run.worker(), "worker-1");
+                assert_eq!(run.command(), "backup");
+                Ok(())
+            }
+            _ => Err(anyhow!("expected the run subcommand")),
+        }
+    }
+
+    #[test]
+    fn tail_works() -> Result<()> {
+        let args = Cli::try_parse_from([env!("CARGO_PKG_NAME"), "tail", "worker-1"])?;
+        match args.sub_cmd() {
+            Subcommands::Tail(tail) => {
+                assert_eq!(tail.worker(), "worker-1");
+                assert!(tail.job().is_none());
+                Ok(())
+            }
+            _ => Err(anyhow!("expected the tail subcommand")),
+        }
+    }
+
+    #[test]
+    fn cancel_works() -> Result<()> {
+        let args = Cli::try_parse_from([
+            env!("CARGO_PKG_NAME"),
+            "cancel",
+            "worker-1",
+            "67e55044-10b1-426f-9247-bb680e5fe0c8",
+        ])?;
+        match args.sub_cmd() {
+            Subcommands::Cancel(cancel) => {
+                assert_eq!(cancel.worker(), "worker-1");
+                assert_eq!(
+                    cancel.id().to_string(),
+                    "67e55044-10b1-426f-9247-bb680e5fe0c8"
+                );
+                Ok(())
+            }
+            _ => Err(anyhow!("expected the cancel subcommand")),
+        }
+    }
+
+    #[test]
+    fn cancel_requires_a_job_id() {
+        assert!(
+            Cli::try_parse_from([env!("CARGO_PKG_NAME"), "cancel", "worker-1", "rustup"]).is_err()
+        );
+    }
+
+    #[test]
+    fn history_works() -> Result<()> {
+        let args = Cli::try_parse_from([
+            env!("CARGO_PKG_NAME"),
+            "history",
+            "--worker",
+            "yoda",
+            "--cmd",
+            "rustup",
+            "--since",
+            "2d",
+            "--failed",
+        ])?;
+        match args.sub_cmd() {
+            Subcommands::History(history) => {
+                assert_eq!(history.worker(), "yoda");
+                assert_eq!(history.cmd().as_deref(), Some("rustup"));
+                assert_eq!(*history.since(), Some(Duration::days(2)));
+                assert!(history.until().is_none());
+                assert!(*history.failed());
+                assert!(!*history.succeeded());
+                Ok(())
+            }
+            _ => Err(anyhow!("expected the history subcommand")),
+        }
+    }
+
+    #[test]
+    fn history_failed_and_succeeded_dont_coexist() {
+        assert!(Cli::try_parse_from([
+            env!("CARGO_PKG_NAME"),
+            "history",
+            "--worker",
+            "yoda",
+            "--failed",
+            "--succeeded",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn parse_age_works() {
+        assert_eq!(parse_age("90s"), Ok(Duration::seconds(90)));
+        assert_eq!(parse_age("30m"), Ok(Duration::minutes(30)));
+        assert_eq!(parse_age("12h"), Ok(Duration::hours(12)));
+        assert_eq!(parse_age("1w"), Ok(Duration::weeks(1)));
+        assert!(parse_age("2").is_err());
+        assert!(parse_age("d").is_err());
+        assert!(parse_age("2y").is_err());
+        assert!(parse_age("-2d").is_err());
+        assert!(parse_age("999999999999999d").is_err());
+        assert!(parse_age("9223372036854775807w").is_err());
+    }
+
     #[test]
     fn quiet_and_verbose_dont_coexist() -> Result<()> {
         match Cli::try_parse_from([env!("CARGO_PKG_NAME"), "-q", "-v", "reload"]) {
@@ -10,8 +10,9 @@
 
 use crate::{
     actor::CommandLine,
+    error::Error,
     model::{
-        cli::{Cli, Subcommands},
+        cli::{Cli, History, Subcommands},
         config::{Config, TomlConfig},
     },
 };
@@ -21,10 +22,13 @@ use anyhow::{Context, Result};
 use awc::{http::Version, Client};
 use clap::Parser;
 use futures::StreamExt;
-use pudlib::{initialize, load, ManagerClientToManagerSession, PudxBinary};
+use pudlib::{
+    initialize, load, JobQuery, ManagerClientToManagerSession, PudxBinary, Sort, StatusFilter,
+};
 #[cfg(unix)]
 use rustls::crypto::aws_lc_rs;
 use std::ffi::OsString;
+use time::{Duration, OffsetDateTime};
 use tokio::sync::mpsc::unbounded_channel;
 #[cfg(unix)]
 use tracing::info;
@@ -65,7 +69,22 @@ where
         Subcommands::Schedules(schedule) => {
             ManagerClientToManagerSession::Schedules(schedule.name().clone())
         }
-        Subcommands::Query(query) => ManagerClientToManagerSession::Query(query.query().clone()),
+        Subcommands::History(history) => {
+            ManagerClientToManagerSession::Query(to_query(history, OffsetDateTime::now_utc())?)
+        }
+        Subcommands::Run(run) => ManagerClientToManagerSession::Run {
+            worker: run.worker().clone(),
+            command: run.command().clone(),
+        },
+        Subcommands::Tail(tail) => ManagerClientToManagerSession::Tail {
+            worker: tail.worker().clone(),
+            job: tail.job().clone(),
+        },
+        Subcommands::Running => ManagerClientToManagerSession::Running,
+        Subcommands::Cancel(cancel) => ManagerClientToManagerSession::Cancel {
+            worker: cancel.worker().clone(),
+            id: *cancel.id(),
+        },
     };
 
     if !args.dry_run() {
@@ -103,14 +122,47 @@ where
             }
         });
 
-        if let Err(e) = sys.run().context("run failed") {
-            error!("{e:?}");
-            error!("should kill sys");
+        match sys.run_with_code().context("run failed") {
+            Ok(0) => {}
+            Ok(status) => return Err(Error::JobStatus { status }.into()),
+            Err(e) => {
+                error!("{e:?}");
+                error!("should kill sys");
+            }
         }
     }
     Ok(())
 }
 
+// Turn the history arguments into a job query, with ages measured back from now
+fn to_query(history: &History, now: OffsetDateTime) -> Result<JobQuery, Error> {
+    let before = |age: &Option<Duration>, arg| {
+        age.map(|age| now.checked_sub(age).ok_or(Error::AgeOutOfRange { arg }))
+            .transpose()
+    };
+    let status = if *history.failed() {
+        StatusFilter::Failed
+    } else if *history.succeeded() {
+        StatusFilter::Succeeded
+    } else {
+        StatusFilter::All
+    };
+    let sort = if *history.oldest_first() {
+        Sort::OldestFirst
+    } else {
+        Sort::NewestFirst
+    };
+    Ok(JobQuery::builder()
+        .worker(history.worker().clone())
+        .name_opt(history.cmd().clone())
+        .since_opt(before(history.since(), "since")?)
+        .until_opt(before(history.until(), "until")?)
+        .status(status)
+        .limit_opt(*history.limit())
+        .sort(sort)
+        .build())
+}
+
 #[cfg(unix)]
 fn install_provider() {
     match aws_lc_rs::default_provider().install_default() {
@@ -121,3 +173,43 @@ fn install_provider() {
 
 #[cfg(windows)]
 fn install_provider() {}
+
+#[cfg(test)]
+mod test {
+    use super::to_query;
+    use crate::model::cli::{Cli, Subcommands};
+    use anyhow::{anyhow, Result};
+    use clap::Parser;
+    use time::{Duration, OffsetDateTime};
+
+    fn history_query(since: &str) -> Result<Option<OffsetDateTime>> {
+        let args = Cli::try_parse_from([
+            env!("CARGO_PKG_NAME"),
+            "history",
+            "--worker",
+            "yoda",
+            "--since",
+            since,
+        ])?;
+        match args.sub_cmd() {
+            Subcommands::History(history) => {
+                Ok(to_query(history, OffsetDateTime::UNIX_EPOCH)?.since())
+            }
+            _ => Err(anyhow!("expected the history subcommand")),
+        }
+    }
+
+    #[test]
+    fn ages_are_measured_back_from_now() -> Result<()> {
+        assert_eq!(
+            history_query("2d")?,
+            Some(OffsetDateTime::UNIX_EPOCH - Duration::days(2))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn ages_before_the_earliest_time_are_an_error() {
+        assert!(history_query("10000000d").is_err());
+    }
+}
@@ -8,22 +8,21 @@
 
 // Errors
 
+use crate::schedule::validate::Diagnostics;
+
 #[derive(thiserror::Error, Debug)]
 pub(crate) enum Error {
     #[error("There is no valid config directory")]
     ConfigDir,
-    #[error("invalid calendar string: '{}'", calendar)]
-    InvalidCalendar { calendar: String },
-    #[error("invalid date string: '{}'", date)]
-    InvalidDate { date: String },
-    #[error("invalid time string: '{}'", time)]
-    InvalidTime { time: String },
-    #[error("invalid first capture")]
-    InvalidFirstCapture,
-    #[error("invalid second capture")]
-    InvalidSecondCapture,
-    #[error("no valid captures")]
-    NoValidCaptures,
-    #[error("invalid range: '{}'", range)]
-    InvalidRange { range: String },
+    #[error("invalid timezone: '{}'", timezone)]
+    InvalidTimezone { timezone: String },
+    #[error("invalid calendar string: '{}', {}", calendar, diagnostics)]
+    InvalidCalendarField {
+        calendar: String,
+        diagnostics: Diagnostics,
+    },
+    #[error("invalid cron expression '{}': {}", expr, reason)]
+    InvalidCron { expr: String, reason: String },
+    #[error("invalid command: {}", reason)]
+    InvalidCommand { reason: String },
 }
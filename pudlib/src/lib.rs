@@ -261,12 +261,24 @@ pub use self::header::header;
 pub use self::log::initialize;
 pub use self::log::Config as LogConfig;
 pub use self::manager::data::JobDoc;
+pub use self::manager::data::RunningJob;
 pub use self::manager::message::ManagerClientToManagerSession;
+pub use self::manager::query::JobQuery;
+pub use self::manager::query::Sort;
+pub use self::manager::query::StatusFilter;
+pub use self::schedule::cron::parse_cron;
+pub use self::schedule::cron::Cron;
 pub use self::schedule::dow::DayOfWeek;
+pub use self::schedule::dow::Occurrence;
 pub use self::schedule::hms::Hour;
 pub use self::schedule::hms::Minute;
 pub use self::schedule::hms::Second;
 pub use self::schedule::parse_calendar;
+pub use self::schedule::parse_calendar_seeded;
+pub use self::schedule::validate::validate_calendar;
+pub use self::schedule::validate::CalendarDiagnostic;
+pub use self::schedule::validate::CalendarField;
+pub use self::schedule::validate_timezone;
 pub use self::schedule::ymd::Day;
 pub use self::schedule::ymd::Month;
 pub use self::schedule::ymd::Year;
@@ -276,8 +288,17 @@ pub use self::server::message::ServerToManagerClient;
 pub use self::server::message::ServerToWorkerClient;
 pub use self::server::message::WorkerSessionToServer;
 pub use self::server::Command;
+pub use self::server::Limits;
+pub use self::server::OutputEncoding;
+pub use self::server::OutputLimits;
+pub use self::server::Overlap;
 pub use self::server::Schedule;
 pub use self::server::Schedules;
 pub use self::utils::parse_ts_ping;
 pub use self::utils::send_ts_ping;
 pub use self::worker::message::WorkerClientToWorkerSession;
+pub use self::worker::JobEvent;
+pub use self::worker::JobExit;
+pub use self::worker::KillReason;
+pub use self::worker::OutputLine;
+pub use self::worker::Stream;
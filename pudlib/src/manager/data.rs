@@ -6,26 +6,154 @@
 // option. All files in the project carrying such notice may not be copied,
 // modified, or distributed except according to those terms.
 
-//! Database document structs
+//! Database document and job status structs
 
+use crate::{JobExit, KillReason, OutputLine, Stream};
 use getset::Getters;
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 use time::OffsetDateTime;
+use typed_builder::TypedBuilder;
+use uuid::Uuid;
 
 /// Job document
 #[derive(Clone, Debug, Deserialize, Getters, Serialize)]
 #[getset(get = "pub")]
 pub struct JobDoc {
+    /// The job id
+    #[serde(default)]
+    id: Uuid,
+    /// The job name
+    #[serde(default)]
+    name: String,
     /// The start time of the job
     #[serde(with = "time::serde::iso8601")]
     start_time: OffsetDateTime,
     /// The end time of the job
     #[serde(with = "time::serde::iso8601")]
     end_time: OffsetDateTime,
-    /// The stdout lines of the job
+    /// The stdout and stderr lines of the job, in the order they were written
+    #[serde(default)]
+    output: Vec<OutputLine>,
+    /// The stdout lines of a job stored before output was interleaved
+    #[serde(default)]
     stdout: Vec<String>,
-    /// The stderr lines of the job
+    /// The stderr lines of a job stored before output was interleaved
+    #[serde(default)]
     stderr: Vec<String>,
-    /// The status code of the job
-    status: i32,
+    /// The number of stdout lines dropped by the output limits
+    #[serde(default)]
+    stdout_dropped: usize,
+    /// The number of stderr lines dropped by the output limits
+    #[serde(default)]
+    stderr_dropped: usize,
+    /// Was any of the output dropped by the output limits
+    #[serde(default)]
+    truncated: bool,
+    /// The status code of the job, if it exited
+    #[serde(default)]
+    status: Option<i32>,
+    /// How the job exited
+    #[serde(default)]
+    exit: Option<JobExit>,
+    /// Why the job was killed, if it was
+    #[serde(default)]
+    killed: Option<KillReason>,
+    /// Was the job skipped because its previous run was still going
+    #[serde(default)]
+    skipped: bool,
+    /// The manager that asked for the job to run now, if it wasn't scheduled
+    #[serde(default)]
+    requested_by: Option<Uuid>,
+    /// The manager that cancelled the job, if it was cancelled
+    #[serde(default)]
+    cancelled_by: Option<Uuid>,
+}
+
+impl JobDoc {
+    /// The output of the job in the order it was written.  Jobs stored before
+    /// output was interleaved have all of their stdout first, then stderr.
+    #[must_use]
+    pub fn transcript(&self) -> Vec<OutputLine> {
+        if !self.output.is_empty() {
+            return self.output.clone();
+        }
+        self.stdout
+            .iter()
+            .map(|line| OutputLine::new(Duration::ZERO, Stream::Stdout, line.clone()))
+            .chain(
+                self.stderr
+                    .iter()
+                    .map(|line| OutputLine::new(Duration::ZERO, Stream::Stderr, line.clone())),
+            )
+            .collect()
+    }
+}
+
+/// A job that is running right now
+#[derive(Clone, Debug, Deserialize, Eq, Getters, PartialEq, Serialize, TypedBuilder)]
+#[getset(get = "pub")]
+pub struct RunningJob {
+    /// The name of the worker running the job
+    #[builder(setter(into))]
+    worker: String,
+    /// The command id associated with this job
+    id: Uuid,
+    /// The job name
+    #[builder(setter(into))]
+    name: String,
+    /// The start time of the job
+    start_time: OffsetDateTime,
+    /// How long the job has been running
+    elapsed: Duration,
+    /// The stdout lines of the job so far
+    stdout_lines: usize,
+    /// The stderr lines of the job so far
+    stderr_lines: usize,
+}
+
+#[cfg(test)]
+mod test {
+    use super::JobDoc;
+    use crate::Stream;
+    use anyhow::Result;
+    use std::time::Duration;
+    use toml::from_str;
+
+    #[test]
+    fn transcript_keeps_interleaving() -> Result<()> {
+        let job: JobDoc = from_str(
+            r#"start_time = "2026-10-16T10:00:00Z"
+end_time = "2026-10-16T10:00:01Z"
+status = 1
+output = [
+    { offset = { secs = 0, nanos = 1000 }, stream = "stdout", line = "building" },
+    { offset = { secs = 0, nanos = 2000 }, stream = "stderr", line = "error" },
+    { offset = { secs = 1, nanos = 0 }, stream = "stdout", line = "done" },
+]
+"#,
+        )?;
+        let transcript = job.transcript();
+        assert_eq!(transcript.len(), 3);
+        assert_eq!(transcript[1].stream(), Stream::Stderr);
+        assert_eq!(transcript[2].offset(), Duration::from_secs(1));
+        Ok(())
+    }
+
+    #[test]
+    fn transcript_of_older_jobs() -> Result<()> {
+        let job: JobDoc = from_str(
+            r#"start_time = "2026-10-16T10:00:00Z"
+end_time = "2026-10-16T10:00:01Z"
+status = 1
+stdout = ["building", "done"]
+stderr = ["error"]
+"#,
+        )?;
+        let transcript = job.transcript();
+        assert_eq!(transcript.len(), 3);
+        assert_eq!(transcript[0].line(), "building");
+        assert_eq!(transcript[2].stream(), Stream::Stderr);
+        Ok(())
+    }
 }
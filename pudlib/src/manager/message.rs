@@ -8,8 +8,10 @@
 
 //! Manager Actix Message
 
+use crate::JobQuery;
 use actix::Message;
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 /// A message from a manger client to a manager session
 #[derive(Clone, Debug, Deserialize, Message, Serialize)]
@@ -23,6 +25,29 @@ pub enum ManagerClientToManagerSession {
     ListWorkers,
     /// List the schedules for the given worker
     Schedules(String),
-    /// The query to run against the job documents
-    Query(String),
+    /// Query the stored jobs of a worker
+    Query(JobQuery),
+    /// Run a command on a worker now
+    Run {
+        /// The name of the worker to run the command on
+        worker: String,
+        /// The name of the command to run
+        command: String,
+    },
+    /// Follow the output of the jobs running on a worker
+    Tail {
+        /// The name of the worker to follow
+        worker: String,
+        /// The job name to follow, or every job when not given
+        job: Option<String>,
+    },
+    /// List the jobs running on every worker
+    Running,
+    /// Cancel a running job on a worker
+    Cancel {
+        /// The name of the worker the job is running on
+        worker: String,
+        /// The command id of the job to cancel
+        id: Uuid,
+    },
 }
@@ -10,3 +10,4 @@
 
 pub(crate) mod data;
 pub(crate) mod message;
+pub(crate) mod query;
@@ -0,0 +1,188 @@
+// Copyright (c) 2022 pud developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Job history queries
+
+use crate::JobDoc;
+use getset::{CopyGetters, Getters};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use typed_builder::TypedBuilder;
+
+/// Which jobs to return, by how they finished
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum StatusFilter {
+    /// Every job
+    #[default]
+    All,
+    /// Jobs that exited with status 0
+    Succeeded,
+    /// Jobs that exited with any other status, or never reported an exit
+    Failed,
+}
+
+/// The order to return jobs in
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Sort {
+    /// The most recently started jobs first
+    #[default]
+    NewestFirst,
+    /// The least recently started jobs first
+    OldestFirst,
+}
+
+/// A query for the stored jobs of a worker
+#[derive(
+    Clone, CopyGetters, Debug, Deserialize, Eq, Getters, PartialEq, Serialize, TypedBuilder,
+)]
+pub struct JobQuery {
+    /// The name of the worker the jobs ran on
+    #[builder(setter(into))]
+    #[getset(get = "pub")]
+    worker: String,
+    /// Only return jobs with this name
+    #[builder(default, setter(strip_option(fallback = name_opt), into))]
+    #[getset(get = "pub")]
+    name: Option<String>,
+    /// Only return jobs started at or after this time
+    #[builder(default, setter(strip_option(fallback = since_opt)))]
+    #[getset(get_copy = "pub")]
+    since: Option<OffsetDateTime>,
+    /// Only return jobs started before this time
+    #[builder(default, setter(strip_option(fallback = until_opt)))]
+    #[getset(get_copy = "pub")]
+    until: Option<OffsetDateTime>,
+    /// Only return jobs that finished this way
+    #[builder(default)]
+    #[getset(get_copy = "pub")]
+    status: StatusFilter,
+    /// Return at most this many jobs
+    #[builder(default, setter(strip_option(fallback = limit_opt)))]
+    #[getset(get_copy = "pub")]
+    limit: Option<usize>,
+    /// The order to return the jobs in
+    #[builder(default)]
+    #[getset(get_copy = "pub")]
+    sort: Sort,
+}
+
+impl JobQuery {
+    /// Does the given job document match this query, ignoring the limit
+    #[must_use]
+    pub fn matches(&self, job: &JobDoc) -> bool {
+        self.name.as_ref().is_none_or(|name| name == job.name())
+            && self.since.is_none_or(|since| *job.start_time() >= since)
+            && self.until.is_none_or(|until| *job.start_time() < until)
+            && match self.status {
+                StatusFilter::All => true,
+                StatusFilter::Succeeded => *job.status() == Some(0) && !*job.skipped(),
+                StatusFilter::Failed => *job.status() != Some(0) && !*job.skipped(),
+            }
+    }
+
+    /// Sort and limit the matching job documents
+    #[must_use]
+    pub fn apply(&self, jobs: impl IntoIterator<Item = JobDoc>) -> Vec<JobDoc> {
+        let mut jobs: Vec<JobDoc> = jobs.into_iter().filter(|job| self.matches(job)).collect();
+        match self.sort {
+            Sort::NewestFirst => jobs.sort_by(|x, y| y.start_time().cmp(x.start_time())),
+            Sort::OldestFirst => jobs.sort_by(|x, y| x.start_time().cmp(y.start_time())),
+        }
+        if let Some(limit) = self.limit {
+            jobs.truncate(limit);
+        }
+        jobs
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{JobQuery, Sort, StatusFilter};
+    use crate::JobDoc;
+    use anyhow::Result;
+    use toml::from_str;
+
+    fn job(name: &str, start_time: &str, status: i32) -> Result<JobDoc> {
+        Ok(from_str(&format!(
+            r#"name = "{name}"
+start_time = "{start_time}"
+end_time = "{start_time}"
+stdout = []
+stderr = []
+status = {status}
+"#
+        ))?)
+    }
+
+    fn jobs() -> Result<Vec<JobDoc>> {
+        Ok(vec![
+            job("rustup", "2026-10-14T10:00:00Z", 0)?,
+            job("rustup", "2026-10-15T10:00:00Z", 1)?,
+            job("backup", "2026-10-16T10:00:00Z", 0)?,
+            job("rustup", "2026-10-16T11:00:00Z", 0)?,
+        ])
+    }
+
+    #[test]
+    fn filters_by_name_and_status() -> Result<()> {
+        let query = JobQuery::builder()
+            .worker("yoda")
+            .name("rustup")
+            .status(StatusFilter::Failed)
+            .build();
+        let found = query.apply(jobs()?);
+        assert_eq!(found.len(), 1);
+        assert_eq!(*found[0].status(), Some(1));
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_status_is_a_failure() -> Result<()> {
+        let lost: JobDoc = from_str(
+            r#"name = "rustup"
+start_time = "2026-10-16T12:00:00Z"
+end_time = "2026-10-16T12:00:00Z"
+"#,
+        )?;
+        let failed = JobQuery::builder()
+            .worker("yoda")
+            .status(StatusFilter::Failed)
+            .build();
+        assert!(failed.matches(&lost));
+        let succeeded = JobQuery::builder()
+            .worker("yoda")
+            .status(StatusFilter::Succeeded)
+            .build();
+        assert!(!succeeded.matches(&lost));
+        Ok(())
+    }
+
+    #[test]
+    fn newest_first_with_limit() -> Result<()> {
+        let query = JobQuery::builder().worker("yoda").limit(2).build();
+        let found = query.apply(jobs()?);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].start_time().hour(), 11);
+        assert_eq!(found[1].name(), "backup");
+        Ok(())
+    }
+
+    #[test]
+    fn oldest_first_since() -> Result<()> {
+        let since = *jobs()?[1].start_time();
+        let query = JobQuery::builder()
+            .worker("yoda")
+            .since(since)
+            .sort(Sort::OldestFirst)
+            .build();
+        let found = query.apply(jobs()?);
+        assert_eq!(found.len(), 3);
+        assert_eq!(*found[0].start_time(), since);
+        Ok(())
+    }
+}
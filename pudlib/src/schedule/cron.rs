@@ -0,0 +1,323 @@
+// Copyright (c) 2022 pud developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+// cron expression helpers
+
+use super::{
+    dow::{DayOfWeek, Occurrence},
+    hms::{Hour, Minute, Second},
+    ymd::{Day, Month},
+    Realtime,
+};
+use crate::error::Error::InvalidCron;
+use anyhow::Result;
+use std::collections::BTreeSet;
+use time_tz::timezones::get_by_name;
+
+const MONTH_NAMES: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const DOW_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// A parsed cron expression
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Cron {
+    /// Run once when the worker starts
+    Reboot,
+    /// Run on a calendar
+    Calendar(Box<Realtime>),
+}
+
+/// parse the given cron expression
+///
+/// Both the five field form, `minute hour day month day-of-week`, and the six
+/// field form with a leading second are supported, along with the `@` aliases,
+/// `?`, `L`, `W` and `#`.  The expression may end with an IANA timezone.
+///
+/// # Errors
+///
+pub fn parse_cron(expr: &str) -> Result<Cron> {
+    let invalid = |reason: String| InvalidCron {
+        expr: expr.to_string(),
+        reason,
+    };
+    let mut parts: Vec<&str> = expr.split_whitespace().collect();
+    let timezone = match parts.last() {
+        Some(last) if parts.len() > 1 && get_by_name(last).is_some() => parts.pop(),
+        _ => None,
+    };
+
+    let fields = match parts[..] {
+        ["@reboot"] => return Ok(Cron::Reboot),
+        ["@yearly" | "@annually"] => ["0", "0", "0", "1", "1", "*"],
+        ["@monthly"] => ["0", "0", "0", "1", "*", "*"],
+        ["@weekly"] => ["0", "0", "0", "*", "*", "0"],
+        ["@daily" | "@midnight"] => ["0", "0", "0", "*", "*", "*"],
+        ["@hourly"] => ["0", "0", "*", "*", "*", "*"],
+        [minute, hour, day, month, dow] => ["0", minute, hour, day, month, dow],
+        [second, minute, hour, day, month, dow] => [second, minute, hour, day, month, dow],
+        _ => return Err(invalid("expected 5 or 6 fields, or an @ alias".to_string()).into()),
+    };
+    let [second, minute, hour, day, month, dow] = fields;
+
+    let second = values(second, 0, 59, &[])
+        .map_err(|reason| invalid(format!("second {reason}")))?
+        .map_or(Second::All, Second::Seconds);
+    let minute = values(minute, 0, 59, &[])
+        .map_err(|reason| invalid(format!("minute {reason}")))?
+        .map_or(Minute::All, Minute::Minutes);
+    let hour = values(hour, 0, 23, &[])
+        .map_err(|reason| invalid(format!("hour {reason}")))?
+        .map_or(Hour::All, Hour::Hours);
+    let month = values(month, 1, 12, &MONTH_NAMES)
+        .map_err(|reason| invalid(format!("month {reason}")))?
+        .map_or(Month::All, Month::Months);
+    let day_of_week =
+        day_of_week(dow).map_err(|reason| invalid(format!("day of week {reason}")))?;
+    let day = day_of_month(day).map_err(|reason| invalid(format!("day {reason}")))?;
+    // cron runs when either day matches if both are restricted
+    let day_or_day_of_week = day != Day::All && day_of_week != DayOfWeek::All;
+
+    let mut realtime = Realtime::builder()
+        .day_of_week(day_of_week)
+        .month(month)
+        .day(day)
+        .hour(hour)
+        .minute(minute)
+        .second(second)
+        .day_or_day_of_week(day_or_day_of_week)
+        .build();
+    realtime.timezone = timezone.map(str::to_string);
+    Ok(Cron::Calendar(Box::new(realtime)))
+}
+
+fn day_of_month(field: &str) -> Result<Day, String> {
+    let upper = field.to_ascii_uppercase();
+    if upper == "L" {
+        Ok(Day::FromLast(vec![1]))
+    } else if upper == "LW" {
+        Ok(Day::LastWeekday)
+    } else if let Some(offset) = upper.strip_prefix("L-") {
+        let offset = value(offset, 0, 30, &[])?;
+        Ok(Day::FromLast(vec![offset + 1]))
+    } else if let Some(day) = upper.strip_suffix('W') {
+        Ok(Day::NearestWeekday(value(day, 1, 31, &[])?))
+    } else {
+        Ok(values(field, 1, 31, &[])?.map_or(Day::All, Day::Days))
+    }
+}
+
+fn day_of_week(field: &str) -> Result<DayOfWeek, String> {
+    if field == "*" || field == "?" {
+        return Ok(DayOfWeek::All);
+    }
+    let mut days = BTreeSet::new();
+    for item in field.split(',') {
+        let upper = item.to_ascii_uppercase();
+        let (item, occurrence) = if let Some((item, nth)) = upper.split_once('#') {
+            match nth.parse::<u8>() {
+                Ok(nth @ 1..=5) => (item.to_string(), Occurrence::Nth(nth)),
+                _ => return Err(format!("occurrence '{nth}' must be 1..5")),
+            }
+        } else if let Some(item) = upper.strip_suffix('L').filter(|item| !item.is_empty()) {
+            (item.to_string(), Occurrence::Last)
+        } else {
+            (upper.clone(), Occurrence::Every)
+        };
+        for day in values(&item, 0, 7, &DOW_NAMES)?.unwrap_or_else(|| (0..7).collect()) {
+            // both 0 and 7 are Sunday
+            _ = days.insert((day % 7, occurrence));
+        }
+    }
+    if days
+        .iter()
+        .all(|(_, occurrence)| *occurrence == Occurrence::Every)
+    {
+        Ok(DayOfWeek::Days(
+            days.into_iter().map(|(day, _)| day).collect(),
+        ))
+    } else {
+        Ok(DayOfWeek::Occurrences(days.into_iter().collect()))
+    }
+}
+
+// Parse a comma separated list of values, ranges and steps.  `None` means
+// every value.
+fn values(field: &str, min: u8, max: u8, names: &[&str]) -> Result<Option<Vec<u8>>, String> {
+    if field == "*" || field == "?" {
+        return Ok(None);
+    }
+    let mut values = BTreeSet::new();
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => match step.parse::<usize>() {
+                Ok(step) if step > 0 => (range, Some(step)),
+                _ => return Err(format!("step '{step}' must be a number greater than 0")),
+            },
+            None => (item, None),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (value(start, min, max, names)?, value(end, min, max, names)?)
+        } else {
+            let start = value(range, min, max, names)?;
+            // a step without a range runs to the end of the field
+            (start, if step.is_some() { max } else { start })
+        };
+        if end < start {
+            return Err(format!("range '{range}' is backwards"));
+        }
+        values.extend((start..=end).step_by(step.unwrap_or(1)));
+    }
+    Ok(Some(values.into_iter().collect()))
+}
+
+fn value(value: &str, min: u8, max: u8, names: &[&str]) -> Result<u8, String> {
+    let lower = value.to_ascii_lowercase();
+    if let Some(idx) = names.iter().position(|name| *name == lower) {
+        // names start at the minimum value, i.e. 'jan' is 1
+        return u8::try_from(idx)
+            .map(|idx| idx + min)
+            .map_err(|e| e.to_string());
+    }
+    match value.parse::<u8>() {
+        Ok(val) if (min..=max).contains(&val) => Ok(val),
+        Ok(val) => Err(format!("{val} out of range {min}..{max}")),
+        Err(_) => Err(format!("'{value}' is not valid")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_cron, Cron};
+    use crate::{
+        schedule::{dow::Occurrence, ymd::Day},
+        DayOfWeek, Realtime,
+    };
+    use anyhow::{anyhow, Result};
+    use time::macros::datetime;
+
+    fn calendar(expr: &str) -> Result<Realtime> {
+        match parse_cron(expr)? {
+            Cron::Calendar(realtime) => Ok(*realtime),
+            Cron::Reboot => Err(anyhow!("unexpected @reboot")),
+        }
+    }
+
+    #[test]
+    fn five_fields() -> Result<()> {
+        let expected = Realtime::builder()
+            .day_of_week((1..=5).collect::<Vec<u8>>())
+            .hour(4)
+            .minute(vec![0, 15, 30, 45])
+            .second(0)
+            .build();
+        assert_eq!(calendar("*/15 4 * * mon-fri")?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn six_fields() -> Result<()> {
+        let expected = Realtime::builder()
+            .month(vec![1, 7])
+            .day(1)
+            .hour(0)
+            .minute(30)
+            .second(10)
+            .build();
+        assert_eq!(calendar("10 30 0 1 JAN,JUL ?")?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn aliases() -> Result<()> {
+        assert_eq!(parse_cron("@reboot")?, Cron::Reboot);
+        assert_eq!(calendar("@daily")?, calendar("0 0 * * *")?);
+        assert_eq!(calendar("@weekly")?, calendar("0 0 * * 7")?);
+        assert_eq!(calendar("@annually")?, calendar("@yearly")?);
+        Ok(())
+    }
+
+    #[test]
+    fn timezone() -> Result<()> {
+        let realtime = calendar("0 4 * * * America/New_York")?;
+        assert_eq!(realtime.timezone(), Some("America/New_York"));
+        Ok(())
+    }
+
+    #[test]
+    fn last_and_weekday() -> Result<()> {
+        assert_eq!(calendar("0 0 L * *")?.day, Day::FromLast(vec![1]));
+        assert_eq!(calendar("0 0 L-2 * *")?.day, Day::FromLast(vec![3]));
+        assert_eq!(calendar("0 0 LW * *")?.day, Day::LastWeekday);
+        assert_eq!(calendar("0 0 15W * *")?.day, Day::NearestWeekday(15));
+        assert_eq!(
+            calendar("0 0 * * 5L,MON#1")?.day_of_week,
+            DayOfWeek::Occurrences(vec![(1, Occurrence::Nth(1)), (5, Occurrence::Last)])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn day_or_day_of_week() -> Result<()> {
+        // the 13th, or any Friday
+        let realtime = calendar("0 12 13 * 5")?;
+        let next: Vec<_> = realtime
+            .upcoming(datetime!(2024-09-01 00:00:00 UTC))
+            .take(3)
+            .collect();
+        assert_eq!(
+            next,
+            vec![
+                datetime!(2024-09-06 12:00:00 UTC),
+                datetime!(2024-09-13 12:00:00 UTC),
+                datetime!(2024-09-20 12:00:00 UTC),
+            ]
+        );
+        // a restricted day with an unrestricted day of week only uses the day
+        let realtime = calendar("0 12 13 * *")?;
+        assert_eq!(
+            realtime.next_after(datetime!(2024-09-01 00:00:00 UTC)),
+            Some(datetime!(2024-09-13 12:00:00 UTC))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn invalid() {
+        for expr in [
+            "* * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "*/0 * * * *",
+            "5-1 * * * *",
+            "* * * * MON#6",
+            "@fortnightly",
+        ] {
+            assert!(parse_cron(expr).is_err(), "{expr} should be invalid");
+        }
+    }
+
+    #[test]
+    fn invalid_message() -> Result<()> {
+        match parse_cron("0 25 * * *") {
+            Ok(_) => Err(anyhow!("this should be a bad cron expression")),
+            Err(e) => {
+                assert_eq!(
+                    format!("{e}"),
+                    "invalid cron expression '0 25 * * *': hour 25 out of range 0..23"
+                );
+                Ok(())
+            }
+        }
+    }
+}
@@ -8,18 +8,9 @@
 
 // realtime day of week helpers
 
-use crate::{
-    error::Error::{InvalidFirstCapture, InvalidRange, InvalidSecondCapture, NoValidCaptures},
-    utils::until_err,
-};
-use anyhow::{anyhow, Result};
-use regex::Regex;
-use std::{collections::HashSet, fmt::Display, sync::LazyLock};
-use time::Weekday;
-
-static DOW_RANGE_RE: LazyLock<Regex> = LazyLock::new(|| {
-    Regex::new(r"([a-zA-Z]{3,})\.\.([a-zA-Z]{3,})").expect("invalid day of week range regex")
-});
+use super::{split, CalendarDiagnostic, CalendarField};
+use std::{collections::BTreeSet, fmt::Display};
+use time::{Date, Weekday};
 
 /// The day of the week for a realtime schedule
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
@@ -28,6 +19,30 @@ pub enum DayOfWeek {
     All,
     /// Specific days of the week
     Days(Vec<u8>),
+    /// Specific occurrences of days of the week within the month, i.e. the
+    /// first Monday or the last Friday
+    Occurrences(Vec<(u8, Occurrence)>),
+}
+
+/// Which occurrences of a day of the week within a month
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Occurrence {
+    /// Every occurrence
+    Every,
+    /// The nth occurrence, starting at 1
+    Nth(u8),
+    /// The last occurrence
+    Last,
+}
+
+impl Occurrence {
+    fn matches(self, date: Date) -> bool {
+        match self {
+            Occurrence::Every => true,
+            Occurrence::Nth(nth) => (date.day() - 1) / 7 + 1 == nth,
+            Occurrence::Last => date.day() + 7 > date.month().length(date.year()),
+        }
+    }
 }
 
 impl Display for DayOfWeek {
@@ -39,15 +54,20 @@ impl Display for DayOfWeek {
             DayOfWeek::Days(vals) => {
                 let len = vals.len();
                 for (idx, val) in vals.iter().enumerate() {
-                    match val {
-                        0 => write!(f, "Sun")?,
-                        1 => write!(f, "Mon")?,
-                        2 => write!(f, "Tue")?,
-                        3 => write!(f, "Wed")?,
-                        4 => write!(f, "Thu")?,
-                        5 => write!(f, "Fri")?,
-                        6 => write!(f, "Sat")?,
-                        _ => write!(f, "Unk")?,
+                    write!(f, "{}", day_name(*val))?;
+                    if idx < len - 1 {
+                        write!(f, ", ")?;
+                    }
+                }
+            }
+            DayOfWeek::Occurrences(vals) => {
+                let len = vals.len();
+                for (idx, (val, occurrence)) in vals.iter().enumerate() {
+                    write!(f, "{}", day_name(*val))?;
+                    match occurrence {
+                        Occurrence::Every => {}
+                        Occurrence::Nth(nth) => write!(f, "#{nth}")?,
+                        Occurrence::Last => write!(f, "#L")?,
                     }
                     if idx < len - 1 {
                         write!(f, ", ")?;
@@ -59,6 +79,19 @@ impl Display for DayOfWeek {
     }
 }
 
+fn day_name(val: u8) -> &'static str {
+    match val {
+        0 => "Sun",
+        1 => "Mon",
+        2 => "Tue",
+        3 => "Wed",
+        4 => "Thu",
+        5 => "Fri",
+        6 => "Sat",
+        _ => "Unk",
+    }
+}
+
 impl From<u8> for DayOfWeek {
     fn from(value: u8) -> Self {
         DayOfWeek::Days(vec![value])
@@ -72,96 +105,141 @@ impl From<Vec<u8>> for DayOfWeek {
 }
 
 impl DayOfWeek {
-    pub(crate) fn matches(&self, given: Weekday) -> bool {
+    pub(crate) fn matches(&self, given: Date) -> bool {
+        let given_u = match given.weekday() {
+            Weekday::Monday => 1,
+            Weekday::Tuesday => 2,
+            Weekday::Wednesday => 3,
+            Weekday::Thursday => 4,
+            Weekday::Friday => 5,
+            Weekday::Saturday => 6,
+            Weekday::Sunday => 0,
+        };
         match self {
             DayOfWeek::All => true,
-            DayOfWeek::Days(days) => {
-                let given_u = match given {
-                    Weekday::Monday => 1,
-                    Weekday::Tuesday => 2,
-                    Weekday::Wednesday => 3,
-                    Weekday::Thursday => 4,
-                    Weekday::Friday => 5,
-                    Weekday::Saturday => 6,
-                    Weekday::Sunday => 0,
-                };
-                days.contains(&given_u)
-            }
+            DayOfWeek::Days(days) => days.contains(&given_u),
+            DayOfWeek::Occurrences(days) => days
+                .iter()
+                .any(|(day, occurrence)| *day == given_u && occurrence.matches(given)),
         }
     }
 }
 
-pub(crate) fn parse_day_of_week(dowish: &str) -> Result<DayOfWeek> {
+pub(crate) fn parse_day_of_week(dowish: &str) -> Result<DayOfWeek, CalendarDiagnostic> {
     if dowish == "*" {
-        Ok(DayOfWeek::All)
-    } else {
-        let mut err = Ok(());
-        let mut dows: Vec<u8> = dowish
-            .split(',')
-            .map(parse_range_or_dow)
-            .scan(&mut err, until_err)
-            .flatten()
-            .collect::<HashSet<_>>()
-            .into_iter()
-            .collect();
-        err?;
-        dows.sort_unstable();
-        Ok(DayOfWeek::Days(dows))
+        return Ok(DayOfWeek::All);
     }
-}
-
-fn parse_range_or_dow(dow_str: &str) -> Result<Vec<u8>> {
-    if DOW_RANGE_RE.is_match(dow_str) {
-        parse_dow_range(dow_str)
-    } else {
-        parse_dow_v(dow_str)
+    let invalid = |offset: usize, reason: String| {
+        CalendarDiagnostic::new(CalendarField::DayOfWeek, offset, reason)
+    };
+    let mut days = BTreeSet::new();
+    for (offset, item) in split(dowish, 0, ',') {
+        // 'Mon#1' or 'Fri#L' limits the days to an occurrence within the month
+        let (range, occurrence) = match item.split_once('#') {
+            Some((range, occurrence)) => match parse_occurrence(occurrence) {
+                Some(occurrence) => (range, occurrence),
+                None => {
+                    return Err(invalid(
+                        offset + range.len() + 1,
+                        format!("occurrence '{occurrence}' must be 1..5 or L"),
+                    ))
+                }
+            },
+            None => (item, Occurrence::Every),
+        };
+        let dow = |offset: usize, dow: &str| {
+            parse_dow(dow).ok_or_else(|| invalid(offset, format!("unknown day of week '{dow}'")))
+        };
+        let (first, last) = if let Some((first, second)) = range.split_once("..") {
+            (dow(offset, first)?, dow(offset + first.len() + 2, second)?)
+        } else {
+            let day = dow(offset, range)?;
+            (day, day)
+        };
+        if last < first {
+            return Err(invalid(
+                offset,
+                format!("day of week range '{range}' is backwards"),
+            ));
+        }
+        days.extend((first..=last).map(|day| (day, occurrence)));
     }
+    Ok(if dowish.contains('#') {
+        DayOfWeek::Occurrences(days.into_iter().collect())
+    } else {
+        DayOfWeek::Days(days.into_iter().map(|(day, _)| day).collect())
+    })
 }
 
-fn parse_dow_range(dow_range: &str) -> Result<Vec<u8>> {
-    let caps = DOW_RANGE_RE.captures(dow_range).ok_or(NoValidCaptures)?;
-    let first = parse_dow(caps.get(1).ok_or(InvalidFirstCapture)?.as_str())?;
-    let second = parse_dow(caps.get(2).ok_or(InvalidSecondCapture)?.as_str())?;
-    if second < first {
-        Err(InvalidRange {
-            range: dow_range.to_string(),
-        }
-        .into())
+fn parse_occurrence(occurrence: &str) -> Option<Occurrence> {
+    if occurrence.eq_ignore_ascii_case("l") {
+        Some(Occurrence::Last)
     } else {
-        Ok((first..=second).collect())
+        match occurrence.parse::<u8>() {
+            Ok(nth @ 1..=5) => Some(Occurrence::Nth(nth)),
+            _ => None,
+        }
     }
 }
 
-fn parse_dow_v(dow: &str) -> Result<Vec<u8>> {
-    parse_dow(dow).map(|x| vec![x])
-}
-
-fn parse_dow(dow: &str) -> Result<u8> {
-    let dow_l = dow.to_ascii_lowercase();
-
-    Ok(if &dow_l == "sun" || &dow_l == "sunday" {
-        0
-    } else if &dow_l == "mon" || &dow_l == "monday" {
-        1
-    } else if &dow_l == "tue" || &dow_l == "tuesday" {
-        2
-    } else if &dow_l == "wed" || &dow_l == "wednesday" {
-        3
-    } else if &dow_l == "thu" || &dow_l == "thursday" {
-        4
-    } else if &dow_l == "fri" || &dow_l == "friday" {
-        5
-    } else if &dow_l == "sat" || &dow_l == "saturday" {
-        6
-    } else {
-        return Err(anyhow!("invalid day of week: {dow}"));
-    })
+fn parse_dow(dow: &str) -> Option<u8> {
+    match dow.to_ascii_lowercase().as_str() {
+        "sun" | "sunday" => Some(0),
+        "mon" | "monday" => Some(1),
+        "tue" | "tuesday" => Some(2),
+        "wed" | "wednesday" => Some(3),
+        "thu" | "thursday" => Some(4),
+        "fri" | "friday" => Some(5),
+        "sat" | "saturday" => Some(6),
+        _ => None,
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{parse_day_of_week, DayOfWeek};
+    use super::{parse_day_of_week, DayOfWeek, Occurrence};
     use anyhow::{anyhow, Result};
+    use time::macros::date;
+
+    #[test]
+    fn occurrences() -> Result<()> {
+        assert_eq!(
+            DayOfWeek::Occurrences(vec![
+                (1, Occurrence::Nth(1)),
+                (2, Occurrence::Every),
+                (5, Occurrence::Last)
+            ]),
+            parse_day_of_week("Mon#1,Fri#L,Tue")?
+        );
+        assert_eq!(
+            DayOfWeek::Occurrences(vec![(1, Occurrence::Nth(2)), (2, Occurrence::Nth(2))]),
+            parse_day_of_week("Mon..Tue#2")?
+        );
+        assert!(parse_day_of_week("Mon#6").is_err());
+        assert!(parse_day_of_week("Mon#0").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn occurrence_matching_works() -> Result<()> {
+        let first_monday = parse_day_of_week("Mon#1")?;
+        assert!(first_monday.matches(date!(2024 - 04 - 01)));
+        assert!(!first_monday.matches(date!(2024 - 04 - 08)));
+        let last_friday = parse_day_of_week("Fri#L")?;
+        assert!(last_friday.matches(date!(2024 - 05 - 31)));
+        assert!(!last_friday.matches(date!(2024 - 05 - 24)));
+        assert!(last_friday.matches(date!(2024 - 02 - 23)));
+        Ok(())
+    }
+
+    #[test]
+    fn occurrence_display() -> Result<()> {
+        assert_eq!(
+            parse_day_of_week("Fri#L,Mon#1")?.to_string(),
+            "Mon#1, Fri#L"
+        );
+        Ok(())
+    }
 
     #[test]
     fn simple() -> Result<()> {
@@ -250,7 +328,7 @@ mod test {
         match parse_day_of_week("Hogwash,Wed") {
             Ok(_) => Err(anyhow!("this day of week should be invalid")),
             Err(e) => {
-                assert_eq!(format!("{e}"), "invalid day of week: Hogwash");
+                assert_eq!(format!("{e}"), "unknown day of week 'Hogwash' at byte 0");
                 Ok(())
             }
         }
@@ -261,7 +339,7 @@ mod test {
         match parse_day_of_week("Mon..Hogwash,Wed") {
             Ok(_) => Err(anyhow!("this day of week should be invalid")),
             Err(e) => {
-                assert_eq!(format!("{e}"), "invalid day of week: Hogwash");
+                assert_eq!(format!("{e}"), "unknown day of week 'Hogwash' at byte 5");
                 Ok(())
             }
         }
@@ -272,7 +350,10 @@ mod test {
         match parse_day_of_week("Fri..Mon") {
             Ok(_) => Err(anyhow!("this day of week should be invalid")),
             Err(e) => {
-                assert_eq!(format!("{e}"), "invalid range: 'Fri..Mon'");
+                assert_eq!(
+                    format!("{e}"),
+                    "day of week range 'Fri..Mon' is backwards at byte 0"
+                );
                 Ok(())
             }
         }
@@ -8,10 +8,9 @@
 
 // realtime HH:MM:SS helpers
 
-use super::{parse_time_chunk, All};
-use crate::error::Error::InvalidTime;
-use anyhow::Result;
-use rand::Rng;
+use super::{
+    parse_time_chunk, pick, split, All, CalendarDiagnostic, CalendarField, Diagnostics, Seed,
+};
 
 const HOURS_PER_DAY: u8 = 24;
 const MINUTES_PER_HOUR: u8 = 60;
@@ -33,6 +32,13 @@ impl Hour {
             Hour::Hours(hours) => hours.contains(&given),
         }
     }
+
+    pub(crate) fn next_match(&self, from: u8) -> Option<u8> {
+        match self {
+            Hour::All => (from < HOURS_PER_DAY).then_some(from),
+            Hour::Hours(hours) => hours.iter().copied().filter(|x| *x >= from).min(),
+        }
+    }
 }
 
 impl All for Hour {
@@ -40,10 +46,8 @@ impl All for Hour {
         Self::All
     }
 
-    fn rand() -> Self {
-        let mut rng = rand::rng();
-        let rand_in_range = rng.random_range(0..24);
-        Hour::Hours(vec![rand_in_range])
+    fn rand(seed: u64) -> Self {
+        Hour::Hours(vec![pick(seed, HOURS_PER_DAY)])
     }
 }
 
@@ -75,6 +79,13 @@ impl Minute {
             Minute::Minutes(minutes) => minutes.contains(&given),
         }
     }
+
+    pub(crate) fn next_match(&self, from: u8) -> Option<u8> {
+        match self {
+            Minute::All => (from < MINUTES_PER_HOUR).then_some(from),
+            Minute::Minutes(minutes) => minutes.iter().copied().filter(|x| *x >= from).min(),
+        }
+    }
 }
 
 impl All for Minute {
@@ -82,10 +93,8 @@ impl All for Minute {
         Self::All
     }
 
-    fn rand() -> Self {
-        let mut rng = rand::rng();
-        let rand_in_range = rng.random_range(0..60);
-        Minute::Minutes(vec![rand_in_range])
+    fn rand(seed: u64) -> Self {
+        Minute::Minutes(vec![pick(seed, MINUTES_PER_HOUR)])
     }
 }
 
@@ -117,6 +126,13 @@ impl Second {
             Second::Seconds(seconds) => seconds.contains(&given),
         }
     }
+
+    pub(crate) fn next_match(&self, from: u8) -> Option<u8> {
+        match self {
+            Second::All => (from < SECONDS_PER_MINUTE).then_some(from),
+            Second::Seconds(seconds) => seconds.iter().copied().filter(|x| *x >= from).min(),
+        }
+    }
 }
 
 impl All for Second {
@@ -124,10 +140,8 @@ impl All for Second {
         Self::All
     }
 
-    fn rand() -> Self {
-        let mut rng = rand::rng();
-        let rand_in_range = rng.random_range(0..60);
-        Second::Seconds(vec![rand_in_range])
+    fn rand(seed: u64) -> Self {
+        Second::Seconds(vec![pick(seed, SECONDS_PER_MINUTE)])
     }
 }
 
@@ -143,31 +157,64 @@ impl From<u8> for Second {
     }
 }
 
-pub(crate) fn parse_hms(hms: &str) -> Result<(Hour, Minute, Second)> {
-    let hms_parts: Vec<&str> = hms.split(':').collect();
-    if hms_parts.len() == 3 {
-        let hour = parse_time_chunk::<Hour>(hms_parts[0], HOURS_PER_DAY, false)?;
-        let minute = parse_time_chunk::<Minute>(hms_parts[1], MINUTES_PER_HOUR, false)?;
-        let second = parse_time_chunk::<Second>(hms_parts[2], SECONDS_PER_MINUTE, false)?;
-        Ok((hour, minute, second))
-    } else {
-        Err(InvalidTime {
-            time: hms.to_string(),
-        }
-        .into())
-    }
+pub(crate) fn parse_hms(hms: &str, seed: Seed) -> Result<(Hour, Minute, Second), Diagnostics> {
+    let parts = split(hms, 0, ':');
+    let [(h_off, hour), (m_off, minute), (s_off, second)] = parts[..] else {
+        return Err(CalendarDiagnostic::new(
+            CalendarField::Time,
+            0,
+            format!("expected a time like 'hour:minute:second', found '{hms}'"),
+        )
+        .into());
+    };
+    let mut diagnostics = Diagnostics::default();
+    let hour = diagnostics.check(
+        h_off,
+        parse_time_chunk::<Hour>(
+            hour,
+            CalendarField::Hour,
+            0,
+            HOURS_PER_DAY - 1,
+            seed.field("hour"),
+        ),
+    );
+    let minute = diagnostics.check(
+        m_off,
+        parse_time_chunk::<Minute>(
+            minute,
+            CalendarField::Minute,
+            0,
+            MINUTES_PER_HOUR - 1,
+            seed.field("minute"),
+        ),
+    );
+    let second = diagnostics.check(
+        s_off,
+        parse_time_chunk::<Second>(
+            second,
+            CalendarField::Second,
+            0,
+            SECONDS_PER_MINUTE - 1,
+            seed.field("second"),
+        ),
+    );
+    let parsed = hour
+        .zip(minute)
+        .zip(second)
+        .map(|((hour, minute), second)| (hour, minute, second));
+    diagnostics.finish(parsed)
 }
 
 #[cfg(test)]
 mod test {
     use super::{
-        parse_hms, Hour, Minute, Second, HOURS_PER_DAY, MINUTES_PER_HOUR, SECONDS_PER_MINUTE,
+        parse_hms, Hour, Minute, Second, Seed, HOURS_PER_DAY, MINUTES_PER_HOUR, SECONDS_PER_MINUTE,
     };
     use anyhow::{anyhow, Result};
 
     #[test]
     fn simple() -> Result<()> {
-        let (hour, minute, second) = parse_hms("10:00:00")?;
+        let (hour, minute, second) = parse_hms("10:00:00", Seed::default())?;
         assert_eq!(hour, Hour::Hours(vec![10]));
         assert_eq!(minute, Minute::Minutes(vec![0]));
         assert_eq!(second, Second::Seconds(vec![0]));
@@ -176,7 +223,7 @@ mod test {
 
     #[test]
     fn range() -> Result<()> {
-        let (hour, minute, second) = parse_hms("9..17:15..45:20..50")?;
+        let (hour, minute, second) = parse_hms("9..17:15..45:20..50", Seed::default())?;
         assert_eq!(hour, Hour::Hours((9..=17).collect()));
         assert_eq!(minute, Minute::Minutes((15..=45).collect()));
         assert_eq!(second, Second::Seconds((20..=50).collect()));
@@ -185,7 +232,7 @@ mod test {
 
     #[test]
     fn simple_repetition() -> Result<()> {
-        let (hour, minute, second) = parse_hms("0/2:0/3:0/4")?;
+        let (hour, minute, second) = parse_hms("0/2:0/3:0/4", Seed::default())?;
         assert_eq!(hour, Hour::Hours((0..HOURS_PER_DAY).step_by(2).collect()));
         assert_eq!(
             minute,
@@ -200,7 +247,7 @@ mod test {
 
     #[test]
     fn range_repetition() -> Result<()> {
-        let (hour, minute, second) = parse_hms("9..17/2:12..44/4:20..50/4")?;
+        let (hour, minute, second) = parse_hms("9..17/2:12..44/4:20..50/4", Seed::default())?;
         assert_eq!(hour, Hour::Hours((9..=17).step_by(2).collect()));
         assert_eq!(minute, Minute::Minutes((12..=44).step_by(4).collect()));
         assert_eq!(second, Second::Seconds((20..=50).step_by(4).collect()));
@@ -209,7 +256,7 @@ mod test {
 
     #[test]
     fn random() -> Result<()> {
-        let (hour, minute, second) = parse_hms("R:R:R")?;
+        let (hour, minute, second) = parse_hms("R:R:R", Seed::default())?;
 
         if let Hour::Hours(vals) = hour {
             assert_eq!(vals.len(), 1);
@@ -234,10 +281,10 @@ mod test {
 
     #[test]
     fn invalid_hour_range() -> Result<()> {
-        match parse_hms("17..9:00:00") {
+        match parse_hms("17..9:00:00", Seed::default()) {
             Ok(_) => Err(anyhow!("this time should be invalid")),
             Err(e) => {
-                assert_eq!(format!("{e}"), "invalid range: '17..9'");
+                assert_eq!(format!("{e}"), "hour range '17..9' is backwards at byte 0");
                 Ok(())
             }
         }
@@ -11,31 +11,25 @@
 use self::{
     dow::{parse_day_of_week, DayOfWeek},
     hms::{parse_hms, Hour, Minute, Second},
+    validate::{split, words, CalendarDiagnostic, CalendarField, Diagnostics},
     ymd::{parse_date, Day, Month, Year},
 };
-use crate::{
-    error::Error::{
-        InvalidCalendar, InvalidFirstCapture, InvalidRange, InvalidSecondCapture, InvalidTime,
-        NoValidCaptures,
-    },
-    utils::until_err,
-};
+use crate::error::Error::{InvalidCalendarField, InvalidTimezone};
 use anyhow::Result;
-use regex::Regex;
-use std::{collections::HashSet, sync::LazyLock};
-use time::OffsetDateTime;
+use std::{collections::BTreeSet, iter::successors};
+use time::{Date, Duration, OffsetDateTime, PrimitiveDateTime, Time};
+use time_tz::{
+    timezones::get_by_name, Offset, OffsetDateTimeExt, OffsetResult, PrimitiveDateTimeExt,
+    TimeZone, Tz,
+};
 use typed_builder::TypedBuilder;
 
+pub(crate) mod cron;
 pub(crate) mod dow;
 pub(crate) mod hms;
+pub(crate) mod validate;
 pub(crate) mod ymd;
 
-static RANGE_RE: LazyLock<Regex> =
-    LazyLock::new(|| Regex::new(r"(\d{1,2})\.\.(\d{1,2})").expect("invalid range regex"));
-static REP_RE: LazyLock<Regex> = LazyLock::new(|| {
-    Regex::new(r"(\d{1,2})(\.\.(\d{1,2}))?/(\d{1,2})").expect("invalid repetition regex")
-});
-
 const MINUTELY: &str = "minutely";
 const HOURLY: &str = "hourly";
 const DAILY: &str = "daily";
@@ -44,10 +38,52 @@ const MONTHLY: &str = "monthly";
 const QUARTERLY: &str = "quarterly";
 const SEMIANUALLY: &str = "semiannually";
 const YEARLY: &str = "yearly";
+const KEYWORDS: [&str; 8] = [
+    MINUTELY,
+    HOURLY,
+    DAILY,
+    WEEKLY,
+    MONTHLY,
+    QUARTERLY,
+    SEMIANUALLY,
+    YEARLY,
+];
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
 
 trait All {
     fn all() -> Self;
-    fn rand() -> Self;
+    fn rand(seed: u64) -> Self;
+}
+
+// The source of the values picked for `R` fields.  The same seed and calendar
+// always hash to the same values, so they are stable across reloads, reconnects
+// and builds, while different seeds spread the runs out.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct Seed(u64);
+
+impl Seed {
+    fn new(seed: &str, calendar: &str) -> Self {
+        let hash = fnv1a(FNV_OFFSET_BASIS, seed.as_bytes());
+        Self(fnv1a(fnv1a(hash, &[0]), calendar.as_bytes()))
+    }
+
+    // A value for the given field, so every `R` in a calendar gets its own
+    pub(crate) fn field(self, field: &str) -> u64 {
+        fnv1a(self.0, field.as_bytes())
+    }
+}
+
+// Pick one of `count` values, starting at 0, from the given seed
+fn pick(seed: u64, count: u8) -> u8 {
+    u8::try_from(seed % u64::from(count)).unwrap_or_default()
+}
+
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME)
+    })
 }
 
 /// A realtime schedule
@@ -74,6 +110,13 @@ pub struct Realtime {
     /// The second(s) to run
     #[builder(default = Second::All, setter(into))]
     second: Second,
+    /// The IANA timezone the calendar is evaluated in
+    #[builder(default, setter(strip_option, into))]
+    timezone: Option<String>,
+    /// Run when either the day or the day of week matches, as cron does when
+    /// both are restricted
+    #[builder(default)]
+    day_or_day_of_week: bool,
 }
 
 impl Default for Realtime {
@@ -86,203 +129,462 @@ impl Default for Realtime {
             hour: Hour::All,
             minute: Minute::All,
             second: Second::All,
+            timezone: None,
+            day_or_day_of_week: false,
         }
     }
 }
 
 impl Realtime {
+    /// Evaluate this schedule in the given timezone if the calendar did not
+    /// specify one
+    #[must_use]
+    pub fn with_default_timezone(mut self, timezone: &str) -> Self {
+        if self.timezone.is_none() {
+            self.timezone = Some(timezone.to_string());
+        }
+        self
+    }
+
+    /// The IANA timezone this schedule is evaluated in, if any
+    #[must_use]
+    pub fn timezone(&self) -> Option<&str> {
+        self.timezone.as_deref()
+    }
+
     /// Should this schedule run at this time
+    ///
+    /// When the schedule has a timezone, `now` is converted to that timezone
+    /// before matching.
     #[must_use]
     pub fn should_run(&self, now: OffsetDateTime) -> bool {
-        self.day_of_week.matches(now.weekday())
-            && self.year.matches(now.year())
+        let now = match self.tz() {
+            Some(tz) => now.to_timezone(tz),
+            None => now,
+        };
+        self.year.matches(now.year())
             && self.month.matches(now.month().into())
-            && self.day.matches(now.day())
+            && self.matches_date(now.date())
             && self.hour.matches(now.hour())
             && self.minute.matches(now.minute())
             && self.second.matches(now.second())
     }
+
+    /// The first time strictly after `after` that this schedule should run
+    ///
+    /// The search walks the calendar fields directly, so it is cheap even when
+    /// the next run is far in the future.  The result is in the same offset as
+    /// `after`.  Returns `None` if the schedule will never run again.
+    ///
+    /// When the schedule has a timezone, a time skipped by a DST transition runs
+    /// at the end of the transition, and a time repeated by a DST transition
+    /// only runs on its first occurrence.
+    #[must_use]
+    pub fn next_after(&self, after: OffsetDateTime) -> Option<OffsetDateTime> {
+        if let Some(tz) = self.tz() {
+            self.next_after_in(after, tz)
+        } else {
+            let start = after
+                .replace_nanosecond(0)
+                .ok()?
+                .checked_add(Duration::SECOND)?;
+            self.next_from(PrimitiveDateTime::new(start.date(), start.time()))
+                .map(|next| next.assume_offset(after.offset()))
+        }
+    }
+
+    fn next_after_in(&self, after: OffsetDateTime, tz: &Tz) -> Option<OffsetDateTime> {
+        let local = after.to_timezone(tz).replace_nanosecond(0).ok()?;
+        let mut start =
+            PrimitiveDateTime::new(local.date(), local.time()).checked_add(Duration::SECOND)?;
+        loop {
+            let wall = self.next_from(start)?;
+            let next = match wall.assume_timezone(tz) {
+                OffsetResult::Some(next) => next,
+                OffsetResult::Ambiguous(first, second) => first.min(second),
+                OffsetResult::None => end_of_gap(wall, tz)?,
+            };
+            // the second occurrence of a repeated time lands here, skip past it
+            if next > after {
+                return Some(next.to_offset(after.offset()));
+            }
+            start = wall.checked_add(Duration::SECOND)?;
+        }
+    }
+
+    /// An iterator over the upcoming times, strictly after `after`, that
+    /// this schedule should run
+    pub fn upcoming(&self, after: OffsetDateTime) -> impl Iterator<Item = OffsetDateTime> + '_ {
+        successors(self.next_after(after), move |prev| self.next_after(*prev))
+    }
+
+    fn tz(&self) -> Option<&'static Tz> {
+        self.timezone.as_deref().and_then(get_by_name)
+    }
+
+    fn matches_date(&self, date: Date) -> bool {
+        if self.day_or_day_of_week {
+            self.day_of_week.matches(date) || self.day.matches(date)
+        } else {
+            self.day_of_week.matches(date) && self.day.matches(date)
+        }
+    }
+
+    // Find the first matching date time at or after `curr`.  When a field has no
+    // match left in its enclosing period, roll over to the start of the next period
+    // and try again.
+    fn next_from(&self, mut curr: PrimitiveDateTime) -> Option<PrimitiveDateTime> {
+        loop {
+            let year = self.year.next_match(curr.year())?;
+            if year != curr.year() {
+                curr = start_of_year(year)?;
+            }
+
+            let month = u8::from(curr.month());
+            match self.month.next_match(month) {
+                Some(next) if next == month => {}
+                Some(next) => {
+                    let next = Date::from_calendar_date(year, next.try_into().ok()?, 1).ok()?;
+                    curr = next.midnight();
+                }
+                None => {
+                    curr = start_of_year(year.checked_add(1)?)?;
+                    continue;
+                }
+            }
+
+            let date = curr.date();
+            let last_day = date.month().length(year);
+            let next_date = (date.day()..=last_day)
+                .filter_map(|day| date.replace_day(day).ok())
+                .find(|date| self.matches_date(*date));
+            match next_date {
+                Some(next) if next == date => {}
+                Some(next) => curr = next.midnight(),
+                None => {
+                    curr = date.replace_day(last_day).ok()?.next_day()?.midnight();
+                    continue;
+                }
+            }
+
+            match self.hour.next_match(curr.hour()) {
+                Some(next) if next == curr.hour() => {}
+                Some(next) => curr = curr.replace_time(Time::from_hms(next, 0, 0).ok()?),
+                None => {
+                    curr = curr.date().next_day()?.midnight();
+                    continue;
+                }
+            }
+
+            match self.minute.next_match(curr.minute()) {
+                Some(next) if next == curr.minute() => {}
+                Some(next) => curr = curr.replace_time(Time::from_hms(curr.hour(), next, 0).ok()?),
+                None => {
+                    curr = curr
+                        .replace_time(Time::from_hms(curr.hour(), 0, 0).ok()?)
+                        .checked_add(Duration::HOUR)?;
+                    continue;
+                }
+            }
+
+            match self.second.next_match(curr.second()) {
+                Some(next) if next == curr.second() => {}
+                Some(next) => curr = curr.replace_second(next).ok()?,
+                None => {
+                    curr = curr.replace_second(0).ok()?.checked_add(Duration::MINUTE)?;
+                    continue;
+                }
+            }
+
+            return Some(curr);
+        }
+    }
+}
+
+fn start_of_year(year: i32) -> Option<PrimitiveDateTime> {
+    Date::from_calendar_date(year, time::Month::January, 1)
+        .ok()
+        .map(Date::midnight)
+}
+
+// `wall` does not exist in `tz` because the clocks jumped forward over it.
+// Find the instant the clocks jumped, i.e. the first instant using the offset
+// in effect after the gap.
+fn end_of_gap(wall: PrimitiveDateTime, tz: &Tz) -> Option<OffsetDateTime> {
+    let offset_at = |at: OffsetDateTime| tz.get_offset_utc(&at).to_utc();
+    let utc = wall.assume_utc();
+    let before = offset_at(utc.checked_sub(Duration::DAY)?);
+    let after = offset_at(utc.checked_add(Duration::DAY)?);
+    // `lo` is always before the jump and `hi` always after it
+    let mut lo = wall.assume_offset(after).unix_timestamp();
+    let mut hi = wall.assume_offset(before).unix_timestamp();
+    while hi - lo > 1 {
+        let mid = lo + (hi - lo) / 2;
+        if offset_at(OffsetDateTime::from_unix_timestamp(mid).ok()?) == after {
+            hi = mid;
+        } else {
+            lo = mid;
+        }
+    }
+    OffsetDateTime::from_unix_timestamp(hi)
+        .ok()
+        .map(|at| at.to_offset(after))
+}
+
+/// Check that the given name is a valid IANA timezone
+///
+/// # Errors
+///
+pub fn validate_timezone(timezone: &str) -> Result<()> {
+    if get_by_name(timezone).is_some() {
+        Ok(())
+    } else {
+        Err(InvalidTimezone {
+            timezone: timezone.to_string(),
+        }
+        .into())
+    }
 }
 
 /// parse the given calendar string
 ///
+/// The calendar may end with an IANA timezone, i.e. `*-*-* 04:00:00 America/New_York`.
+/// Days may be counted back from the end of the month with `~`, i.e. `*-*~1` is
+/// the last day of every month, and days of the week may be limited to an
+/// occurrence within the month, i.e. `Mon#1` or `Fri#L`.
+/// `R` fields are derived from the calendar alone, see [`parse_calendar_seeded`].
+///
 /// # Errors
 ///
 pub fn parse_calendar(calendar: &str) -> Result<Realtime> {
-    let parts: Vec<&str> = calendar.split_whitespace().collect();
-
-    let (day_of_week, date, hms) = if parts.len() == 3 {
-        // has day of week
-        (parts[0], parts[1], parts[2])
-    } else if parts.len() == 2 {
-        // no day of week
-        ("*", parts[0], parts[1])
-    } else if parts.len() == 1 {
-        // no day of week, or date
-        if parts[0] == MINUTELY {
-            return Ok(Realtime::builder().second(0).build());
-        } else if parts[0] == HOURLY {
-            return Ok(Realtime::builder().minute(0).second(0).build());
-        } else if parts[0] == DAILY {
-            return Ok(Realtime::builder().hour(0).minute(0).second(0).build());
-        } else if parts[0] == WEEKLY {
-            return Ok(Realtime::builder()
-                .day_of_week(1)
-                .hour(0)
-                .minute(0)
-                .second(0)
-                .build());
-        } else if parts[0] == MONTHLY {
-            return Ok(Realtime::builder()
-                .day(1)
-                .hour(0)
-                .minute(0)
-                .second(0)
-                .build());
-        } else if parts[0] == QUARTERLY {
-            return Ok(Realtime::builder()
-                .month(vec![1, 4, 7, 10])
-                .day(1)
-                .hour(0)
-                .minute(0)
-                .second(0)
-                .build());
-        } else if parts[0] == SEMIANUALLY {
-            return Ok(Realtime::builder()
-                .month(vec![1, 7])
-                .day(1)
-                .hour(0)
-                .minute(0)
-                .second(0)
-                .build());
-        } else if parts[0] == YEARLY {
-            return Ok(Realtime::builder()
-                .month(1)
-                .day(1)
-                .hour(0)
-                .minute(0)
-                .second(0)
-                .build());
-        }
-        ("*", "*", parts[0])
-    } else {
-        return Err(InvalidCalendar {
+    parse_calendar_seeded(calendar, "")
+}
+
+/// parse the given calendar string, deriving the value of any `R` field from a
+/// hash of `seed` and the calendar
+///
+/// Seeding with the worker name gives each worker its own, stable, run times.
+///
+/// # Errors
+///
+/// Returns every problem found, see [`validate_calendar`](validate::validate_calendar).
+///
+pub fn parse_calendar_seeded(calendar: &str, seed: &str) -> Result<Realtime> {
+    parse(calendar, Seed::new(seed, calendar)).map_err(|diagnostics| {
+        InvalidCalendarField {
             calendar: calendar.to_string(),
+            diagnostics,
+        }
+        .into()
+    })
+}
+
+// Parse the calendar, carrying on past a bad field so that every problem is
+// found
+fn parse(calendar: &str, seed: Seed) -> Result<Realtime, Diagnostics> {
+    let mut diagnostics = Diagnostics::default();
+    let mut parts = words(calendar);
+
+    // Every time part contains a ':' and the keywords stand alone, so a trailing
+    // part without one must be a timezone.
+    let timezone = match parts[..] {
+        [.., (offset, last)] if (2..=4).contains(&parts.len()) && !last.contains(':') => {
+            if validate_timezone(last).is_err() {
+                diagnostics.push(
+                    CalendarField::Timezone,
+                    offset,
+                    format!("unknown timezone '{last}'"),
+                );
+            }
+            _ = parts.pop();
+            Some(last)
+        }
+        _ => None,
+    };
+
+    let realtime = match parts[..] {
+        [(_, keyword)] if KEYWORDS.contains(&keyword) => parse_keyword(keyword),
+        [hms] => parse_fields(&mut diagnostics, None, None, hms, seed),
+        [date, hms] => parse_fields(&mut diagnostics, None, Some(date), hms, seed),
+        [dow, date, hms] => parse_fields(&mut diagnostics, Some(dow), Some(date), hms, seed),
+        _ => {
+            diagnostics.push(
+                CalendarField::Calendar,
+                0,
+                "expected '[day of week] [date] time [timezone]' or a keyword",
+            );
+            None
         }
-        .into());
     };
 
-    let dow = parse_day_of_week(day_of_week)?;
-    let (year, month, day) = parse_date(date)?;
-    let (hour, minute, second) = parse_hms(hms)?;
-    Ok(Realtime::builder()
-        .day_of_week(dow)
-        .year(year)
-        .month(month)
-        .day(day)
-        .hour(hour)
-        .minute(minute)
-        .second(second)
-        .build())
+    let mut realtime = diagnostics.finish(realtime)?;
+    realtime.timezone = timezone.map(str::to_string);
+    Ok(realtime)
 }
 
-fn parse_time_chunk<T>(part: &str, max: u8, one_based: bool) -> Result<T>
+fn parse_keyword(keyword: &str) -> Option<Realtime> {
+    Some(match keyword {
+        MINUTELY => Realtime::builder().second(0).build(),
+        HOURLY => Realtime::builder().minute(0).second(0).build(),
+        WEEKLY => Realtime::builder()
+            .day_of_week(1)
+            .hour(0)
+            .minute(0)
+            .second(0)
+            .build(),
+        MONTHLY => Realtime::builder()
+            .day(1)
+            .hour(0)
+            .minute(0)
+            .second(0)
+            .build(),
+        QUARTERLY => Realtime::builder()
+            .month(vec![1, 4, 7, 10])
+            .day(1)
+            .hour(0)
+            .minute(0)
+            .second(0)
+            .build(),
+        SEMIANUALLY => Realtime::builder()
+            .month(vec![1, 7])
+            .day(1)
+            .hour(0)
+            .minute(0)
+            .second(0)
+            .build(),
+        YEARLY => Realtime::builder()
+            .month(1)
+            .day(1)
+            .hour(0)
+            .minute(0)
+            .second(0)
+            .build(),
+        DAILY => Realtime::builder().hour(0).minute(0).second(0).build(),
+        _ => return None,
+    })
+}
+
+// Parse the day of week, date and time parts, each given with its offset into
+// the calendar.  A missing day of week or date matches every day.
+fn parse_fields(
+    diagnostics: &mut Diagnostics,
+    dow: Option<(usize, &str)>,
+    date: Option<(usize, &str)>,
+    (offset, hms): (usize, &str),
+    seed: Seed,
+) -> Option<Realtime> {
+    let dow = match dow {
+        Some((offset, dow)) => diagnostics.check(offset, parse_day_of_week(dow)),
+        None => Some(DayOfWeek::All),
+    };
+    let date = match date {
+        Some((offset, date)) => diagnostics.check(offset, parse_date(date, seed)),
+        None => Some((Year::All, Month::All, Day::All)),
+    };
+    let (hour, minute, second) = diagnostics.check(offset, parse_hms(hms, seed))?;
+    let (year, month, day) = date?;
+    Some(
+        Realtime::builder()
+            .day_of_week(dow?)
+            .year(year)
+            .month(month)
+            .day(day)
+            .hour(hour)
+            .minute(minute)
+            .second(second)
+            .build(),
+    )
+}
+
+// Parse a comma separated list of values, ranges and repetitions between `min`
+// and `max`
+fn parse_time_chunk<T>(
+    part: &str,
+    field: CalendarField,
+    min: u8,
+    max: u8,
+    seed: u64,
+) -> Result<T, CalendarDiagnostic>
 where
     T: All + From<Vec<u8>>,
 {
     if part == "*" {
         Ok(T::all())
     } else if part == "R" {
-        Ok(T::rand())
-    } else {
-        let mut err = Ok(());
-        let prrv_fn = |hour: &str| -> Result<Vec<u8>> { parse_rep_range_val(hour, max, one_based) };
-        let mut time_v: Vec<u8> = part
-            .split(',')
-            .map(prrv_fn)
-            .scan(&mut err, until_err)
-            .flatten()
-            .collect::<HashSet<_>>()
-            .into_iter()
-            .collect();
-        err?;
-        time_v.sort_unstable();
-        Ok(T::from(time_v))
-    }
-}
-
-fn parse_rep_range_val(val: &str, max: u8, one_based: bool) -> Result<Vec<u8>> {
-    if REP_RE.is_match(val) {
-        parse_repetition(val, max)
-    } else if RANGE_RE.is_match(val) {
-        parse_range(val, max, one_based)
+        Ok(T::rand(seed))
     } else {
-        parse_value(val)
-    }
-}
-
-fn parse_range(range: &str, max: u8, one_based: bool) -> Result<Vec<u8>> {
-    let caps = RANGE_RE.captures(range).ok_or(NoValidCaptures)?;
-    let first = caps
-        .get(1)
-        .ok_or(InvalidFirstCapture)?
-        .as_str()
-        .parse::<u8>()?;
-    let second = caps
-        .get(2)
-        .ok_or(InvalidSecondCapture)?
-        .as_str()
-        .parse::<u8>()?;
-    if second < first
-        || (one_based && first == 0)
-        || ((one_based && second > max) || (!one_based && second >= max))
-    {
-        Err(InvalidRange {
-            range: range.to_string(),
+        let mut values = BTreeSet::new();
+        for (offset, item) in split(part, 0, ',') {
+            values.extend(parse_rep_range_val(item, offset, field, min, max)?);
         }
-        .into())
-    } else {
-        Ok((first..=second).collect())
+        Ok(T::from(values.into_iter().collect()))
     }
 }
 
-fn parse_repetition(rep: &str, max: u8) -> Result<Vec<u8>> {
-    let caps = REP_RE.captures(rep).ok_or(NoValidCaptures)?;
-
-    if caps.len() == 5 {
-        let start = caps
-            .get(1)
-            .ok_or(InvalidFirstCapture)?
-            .as_str()
-            .parse::<u8>()?;
-        let rep = caps
-            .get(4)
-            .ok_or(InvalidSecondCapture)?
-            .as_str()
-            .parse::<usize>()?;
-        if let Some(end) = caps.get(3) {
-            let end = end.as_str().parse::<u8>()?;
-            if end < start || end >= max {
-                Err(InvalidRange {
-                    range: format!("{start}..{end}"),
-                }
-                .into())
-            } else {
-                Ok((start..=end).step_by(rep).collect())
-            }
-        } else {
-            Ok((start..max).step_by(rep).collect())
-        }
+fn parse_rep_range_val(
+    item: &str,
+    offset: usize,
+    field: CalendarField,
+    min: u8,
+    max: u8,
+) -> Result<Vec<u8>, CalendarDiagnostic> {
+    let (range, rep) = match item.split_once('/') {
+        Some((range, rep)) => (range, Some((offset + range.len() + 1, rep))),
+        None => (item, None),
+    };
+    let (start, end) = if let Some((first, second)) = range.split_once("..") {
+        (
+            parse_value(first, offset, field, min, max)?,
+            parse_value(second, offset + first.len() + 2, field, min, max)?,
+        )
     } else {
-        Err(InvalidTime {
-            time: rep.to_string(),
-        }
-        .into())
+        let start = parse_value(range, offset, field, min, max)?;
+        // a repetition without an end runs to the end of the field
+        (start, if rep.is_some() { max } else { start })
+    };
+    if end < start {
+        return Err(CalendarDiagnostic::new(
+            field,
+            offset,
+            format!("{field} range '{range}' is backwards"),
+        ));
     }
+    let step = match rep {
+        Some((offset, rep)) => match rep.parse::<u8>() {
+            Ok(step) if step > 0 => usize::from(step),
+            _ => {
+                return Err(CalendarDiagnostic::new(
+                    field,
+                    offset,
+                    format!("repetition '{rep}' must be a number greater than 0"),
+                ))
+            }
+        },
+        None => 1,
+    };
+    Ok((start..=end).step_by(step).collect())
 }
 
-fn parse_value(value: &str) -> Result<Vec<u8>> {
-    Ok(vec![value.parse::<u8>()?])
+fn parse_value(
+    value: &str,
+    offset: usize,
+    field: CalendarField,
+    min: u8,
+    max: u8,
+) -> Result<u8, CalendarDiagnostic> {
+    match value.parse::<u8>() {
+        Ok(val) if (min..=max).contains(&val) => Ok(val),
+        Ok(val) => Err(CalendarDiagnostic::new(
+            field,
+            offset,
+            format!("{field} {val} out of range {min}..{max}"),
+        )),
+        Err(_) => Err(CalendarDiagnostic::new(
+            field,
+            offset,
+            format!("invalid {field} '{value}'"),
+        )),
+    }
 }
 
 #[cfg(test)]
@@ -290,11 +592,12 @@ mod test {
     use crate::DayOfWeek;
 
     use super::{
-        parse_calendar, Realtime, DAILY, HOURLY, MINUTELY, MONTHLY, QUARTERLY, SEMIANUALLY, WEEKLY,
-        YEARLY,
+        parse_calendar, parse_calendar_seeded, Realtime, DAILY, HOURLY, MINUTELY, MONTHLY,
+        QUARTERLY, SEMIANUALLY, WEEKLY, YEARLY,
     };
     use anyhow::{anyhow, Result};
-    use time::OffsetDateTime;
+    use std::collections::HashSet;
+    use time::{macros::datetime, OffsetDateTime};
 
     #[test]
     fn invalid_calendar() -> Result<()> {
@@ -303,7 +606,7 @@ mod test {
             Err(e) => {
                 assert_eq!(
                     format!("{e}"),
-                    "invalid calendar string: 'this is a bad calendar'"
+                    "invalid calendar string: 'this is a bad calendar', expected '[day of week] [date] time [timezone]' or a keyword at byte 0"
                 );
                 Ok(())
             }
@@ -417,6 +720,29 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn random_is_stable() -> Result<()> {
+        let first = parse_calendar_seeded("*-*-* R:R:R", "yoda")?;
+        let second = parse_calendar_seeded("*-*-* R:R:R", "yoda")?;
+        assert_eq!(first, second);
+        assert_eq!(
+            parse_calendar("*-R-R R:R:R")?,
+            parse_calendar("*-R-R R:R:R")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn random_depends_on_seed() -> Result<()> {
+        let calendar = "*-*-* *:R:R";
+        let runs: HashSet<Realtime> = ["yoda", "luke", "leia", "han", "chewie"]
+            .iter()
+            .map(|seed| parse_calendar_seeded(calendar, seed))
+            .collect::<Result<_>>()?;
+        assert!(runs.len() > 1);
+        Ok(())
+    }
+
     #[test]
     fn full_calendar() -> Result<()> {
         let res = parse_calendar("Mon..Fri *-*-* 3:22:17")?;
@@ -448,7 +774,10 @@ mod test {
         match parse_calendar("*-* 3:11:17") {
             Ok(_) => Err(anyhow!("this should be a bad calendar")),
             Err(e) => {
-                assert_eq!(format!("{e}"), "invalid date string: '*-*'");
+                assert_eq!(
+                    format!("{e}"),
+                    "invalid calendar string: '*-* 3:11:17', expected a date like 'year-month-day', found '*-*' at byte 0"
+                );
                 Ok(())
             }
         }
@@ -459,12 +788,34 @@ mod test {
         match parse_calendar("*-*-* 12:00") {
             Ok(_) => Err(anyhow!("this should be a bad calendar")),
             Err(e) => {
-                assert_eq!(format!("{e}"), "invalid time string: '12:00'");
+                assert_eq!(
+                    format!("{e}"),
+                    "invalid calendar string: '*-*-* 12:00', expected a time like 'hour:minute:second', found '12:00' at byte 6"
+                );
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn impossible_date() -> Result<()> {
+        match parse_calendar("*-02-30 00:00:00") {
+            Ok(_) => Err(anyhow!("this should be a bad calendar")),
+            Err(e) => {
+                assert_eq!(
+                    format!("{e}"),
+                    "invalid calendar string: '*-02-30 00:00:00', February never has 30 days at byte 5"
+                );
                 Ok(())
             }
         }
     }
 
+    #[test]
+    fn zero_repetition() {
+        assert!(parse_calendar("*-*-* *:*:0/0").is_err());
+    }
+
     #[test]
     fn should_run() -> Result<()> {
         let rt = Realtime::builder().hour(4).minute(37).second(0).build();
@@ -482,4 +833,202 @@ mod test {
         assert!(rt.should_run(odt));
         Ok(())
     }
+
+    #[test]
+    fn next_after_same_day() -> Result<()> {
+        let rt = parse_calendar("*-*-* 4:30:00")?;
+        let next = rt.next_after(datetime!(2024-02-10 01:17:42.5 UTC));
+        assert_eq!(next, Some(datetime!(2024-02-10 04:30:00 UTC)));
+        Ok(())
+    }
+
+    #[test]
+    fn next_after_is_strictly_after() -> Result<()> {
+        let rt = parse_calendar("*-*-* 4:30:00")?;
+        let next = rt.next_after(datetime!(2024-02-10 04:30:00 UTC));
+        assert_eq!(next, Some(datetime!(2024-02-11 04:30:00 UTC)));
+        Ok(())
+    }
+
+    #[test]
+    fn next_after_rolls_over_year() -> Result<()> {
+        let rt = parse_calendar(MONTHLY)?;
+        let next = rt.next_after(datetime!(2023-12-31 23:59:59 UTC));
+        assert_eq!(next, Some(datetime!(2024-01-01 00:00:00 UTC)));
+        Ok(())
+    }
+
+    #[test]
+    fn next_after_leap_day() -> Result<()> {
+        let rt = parse_calendar("*-02-29 12:00:00")?;
+        let next = rt.next_after(datetime!(2021-03-01 00:00:00 UTC));
+        assert_eq!(next, Some(datetime!(2024-02-29 12:00:00 UTC)));
+        Ok(())
+    }
+
+    #[test]
+    fn next_after_day_of_week() -> Result<()> {
+        let rt = parse_calendar("Sat,Sun *-*-* 10:00:00")?;
+        // 2024-02-12 is a Monday
+        let next = rt.next_after(datetime!(2024-02-12 11:00:00 UTC));
+        assert_eq!(next, Some(datetime!(2024-02-17 10:00:00 UTC)));
+        Ok(())
+    }
+
+    #[test]
+    fn next_after_keeps_offset() -> Result<()> {
+        let rt = parse_calendar(HOURLY)?;
+        let next = rt.next_after(datetime!(2024-02-12 11:15:00 -5));
+        assert_eq!(next, Some(datetime!(2024-02-12 12:00:00 -5)));
+        Ok(())
+    }
+
+    #[test]
+    fn next_after_never() -> Result<()> {
+        let rt = parse_calendar("2020-*-* 00:00:00")?;
+        assert!(rt.next_after(datetime!(2024-02-12 11:15:00 UTC)).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn next_after_last_day_of_month() -> Result<()> {
+        let rt = parse_calendar("*-*~1 23:00:00")?;
+        let runs: Vec<OffsetDateTime> = rt
+            .upcoming(datetime!(2024-01-31 23:00:00 UTC))
+            .take(3)
+            .collect();
+        assert_eq!(
+            runs,
+            vec![
+                datetime!(2024-02-29 23:00:00 UTC),
+                datetime!(2024-03-31 23:00:00 UTC),
+                datetime!(2024-04-30 23:00:00 UTC),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn next_after_nth_weekday() -> Result<()> {
+        let rt = parse_calendar("Mon#1 *-*-* 09:00:00")?;
+        let next = rt.next_after(datetime!(2024-04-02 00:00:00 UTC));
+        assert_eq!(next, Some(datetime!(2024-05-06 09:00:00 UTC)));
+        let rt = parse_calendar("Fri#L *-*-* 17:00:00")?;
+        let next = rt.next_after(datetime!(2024-05-24 18:00:00 UTC));
+        assert_eq!(next, Some(datetime!(2024-05-31 17:00:00 UTC)));
+        assert!(rt.should_run(datetime!(2024-05-31 17:00:00 UTC)));
+        assert!(!rt.should_run(datetime!(2024-05-24 17:00:00 UTC)));
+        Ok(())
+    }
+
+    #[test]
+    fn upcoming() -> Result<()> {
+        let rt = parse_calendar("*-*-* *:0/20:00")?;
+        let upcoming: Vec<OffsetDateTime> = rt
+            .upcoming(datetime!(2024-02-12 23:30:00 UTC))
+            .take(3)
+            .collect();
+        assert_eq!(
+            upcoming,
+            vec![
+                datetime!(2024-02-12 23:40:00 UTC),
+                datetime!(2024-02-13 00:00:00 UTC),
+                datetime!(2024-02-13 00:20:00 UTC),
+            ]
+        );
+        assert!(upcoming.iter().all(|next| rt.should_run(*next)));
+        Ok(())
+    }
+
+    #[test]
+    fn timezone_suffix() -> Result<()> {
+        let rt = parse_calendar("*-*-* 04:00:00 America/New_York")?;
+        let expected = Realtime::builder()
+            .hour(4)
+            .minute(0)
+            .second(0)
+            .timezone("America/New_York")
+            .build();
+        assert_eq!(rt, expected);
+        let rt = parse_calendar("daily Europe/Berlin")?;
+        assert_eq!(rt.timezone(), Some("Europe/Berlin"));
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_timezone() -> Result<()> {
+        match parse_calendar("*-*-* 04:00:00 Bogus/Zone") {
+            Ok(_) => Err(anyhow!("this should be a bad timezone")),
+            Err(e) => {
+                assert_eq!(
+                    format!("{e}"),
+                    "invalid calendar string: '*-*-* 04:00:00 Bogus/Zone', unknown timezone 'Bogus/Zone' at byte 15"
+                );
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn default_timezone() -> Result<()> {
+        let rt = parse_calendar(DAILY)?.with_default_timezone("America/New_York");
+        assert_eq!(rt.timezone(), Some("America/New_York"));
+        let rt = parse_calendar("daily UTC")?.with_default_timezone("America/New_York");
+        assert_eq!(rt.timezone(), Some("UTC"));
+        Ok(())
+    }
+
+    #[test]
+    fn should_run_in_timezone() -> Result<()> {
+        let rt = parse_calendar("*-*-* 04:00:00 America/New_York")?;
+        assert!(rt.should_run(datetime!(2024-02-12 09:00:00 UTC)));
+        assert!(!rt.should_run(datetime!(2024-02-12 04:00:00 UTC)));
+        Ok(())
+    }
+
+    #[test]
+    fn next_after_in_timezone() -> Result<()> {
+        let rt = parse_calendar("*-*-* 04:00:00 America/New_York")?;
+        let next = rt.next_after(datetime!(2024-02-12 00:00:00 UTC));
+        assert_eq!(next, Some(datetime!(2024-02-12 09:00:00 UTC)));
+        Ok(())
+    }
+
+    #[test]
+    fn next_after_skipped_hour() -> Result<()> {
+        // 02:30 does not exist on 2024-03-10 in New York, run when the clocks jump
+        let rt = parse_calendar("*-*-* 02:30:00 America/New_York")?;
+        let runs: Vec<OffsetDateTime> = rt
+            .upcoming(datetime!(2024-03-09 12:00:00 -5))
+            .take(3)
+            .collect();
+        assert_eq!(
+            runs,
+            vec![
+                datetime!(2024-03-10 03:00:00 -4),
+                datetime!(2024-03-11 02:30:00 -4),
+                datetime!(2024-03-12 02:30:00 -4),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn next_after_repeated_hour() -> Result<()> {
+        // 01:30 happens twice on 2024-11-03 in New York, only run the first
+        let rt = parse_calendar("*-*-* 01:30:00 America/New_York")?;
+        let runs: Vec<OffsetDateTime> = rt
+            .upcoming(datetime!(2024-11-02 12:00:00 -4))
+            .take(3)
+            .collect();
+        assert_eq!(
+            runs,
+            vec![
+                datetime!(2024-11-03 01:30:00 -4),
+                datetime!(2024-11-04 01:30:00 -5),
+                datetime!(2024-11-05 01:30:00 -5),
+            ]
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,330 @@
+// Copyright (c) 2022 pud developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+// realtime calendar validation
+
+use super::{parse, Seed};
+use getset::{CopyGetters, Getters};
+use std::fmt::Display;
+
+/// The part of a calendar string a diagnostic refers to
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CalendarField {
+    /// The calendar as a whole
+    Calendar,
+    /// The day of week part
+    DayOfWeek,
+    /// The date part
+    Date,
+    /// The year of the date
+    Year,
+    /// The month of the date
+    Month,
+    /// The day of the date
+    Day,
+    /// The time part
+    Time,
+    /// The hour of the time
+    Hour,
+    /// The minute of the time
+    Minute,
+    /// The second of the time
+    Second,
+    /// The timezone suffix
+    Timezone,
+}
+
+impl Display for CalendarField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            CalendarField::Calendar => "calendar",
+            CalendarField::DayOfWeek => "day of week",
+            CalendarField::Date => "date",
+            CalendarField::Year => "year",
+            CalendarField::Month => "month",
+            CalendarField::Day => "day",
+            CalendarField::Time => "time",
+            CalendarField::Hour => "hour",
+            CalendarField::Minute => "minute",
+            CalendarField::Second => "second",
+            CalendarField::Timezone => "timezone",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// A problem found while validating a calendar string
+#[derive(Clone, CopyGetters, Debug, Eq, Getters, Hash, PartialEq)]
+pub struct CalendarDiagnostic {
+    /// The field the problem was found in
+    #[getset(get_copy = "pub")]
+    field: CalendarField,
+    /// The byte offset into the calendar string where the problem starts
+    #[getset(get_copy = "pub")]
+    offset: usize,
+    /// Why the calendar is invalid
+    #[getset(get = "pub")]
+    reason: String,
+}
+
+impl CalendarDiagnostic {
+    pub(crate) fn new<T>(field: CalendarField, offset: usize, reason: T) -> Self
+    where
+        T: Into<String>,
+    {
+        Self {
+            field,
+            offset,
+            reason: reason.into(),
+        }
+    }
+}
+
+impl Display for CalendarDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at byte {}", self.reason, self.offset)
+    }
+}
+
+impl std::error::Error for CalendarDiagnostic {}
+
+// Every problem the parser found in a calendar, or in the part of one it was
+// given.  Offsets are from the start of the text that was parsed.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct Diagnostics(Vec<CalendarDiagnostic>);
+
+impl Diagnostics {
+    pub(crate) fn push<T>(&mut self, field: CalendarField, offset: usize, reason: T)
+    where
+        T: Into<String>,
+    {
+        self.0.push(CalendarDiagnostic::new(field, offset, reason));
+    }
+
+    // Keep the value parsed from the part starting at `offset`, or the problems
+    // that stopped it
+    pub(crate) fn check<T, E>(&mut self, offset: usize, parsed: Result<T, E>) -> Option<T>
+    where
+        E: Into<Diagnostics>,
+    {
+        parsed
+            .map_err(|e| {
+                self.0.extend(e.into().0.into_iter().map(|mut diagnostic| {
+                    diagnostic.offset += offset;
+                    diagnostic
+                }));
+            })
+            .ok()
+    }
+
+    // The parsed value, as long as no problem was found along the way
+    pub(crate) fn finish<T>(self, parsed: Option<T>) -> Result<T, Self> {
+        match parsed {
+            Some(parsed) if self.0.is_empty() => Ok(parsed),
+            _ => Err(self),
+        }
+    }
+}
+
+impl From<CalendarDiagnostic> for Diagnostics {
+    fn from(diagnostic: CalendarDiagnostic) -> Self {
+        Self(vec![diagnostic])
+    }
+}
+
+impl Display for Diagnostics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (idx, diagnostic) in self.0.iter().enumerate() {
+            if idx > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{diagnostic}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+// Split `value`, which starts at byte `offset`, on `sep`, keeping the offset of
+// each piece
+pub(crate) fn split(value: &str, offset: usize, sep: char) -> Vec<(usize, &str)> {
+    let mut next = offset;
+    value
+        .split(sep)
+        .map(|piece| {
+            let start = next;
+            next += piece.len() + sep.len_utf8();
+            (start, piece)
+        })
+        .collect()
+}
+
+// Split the calendar on whitespace, keeping the offset of each part
+pub(crate) fn words(calendar: &str) -> Vec<(usize, &str)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (idx, ch) in calendar.char_indices() {
+        if ch.is_whitespace() {
+            if let Some(start) = start.take() {
+                words.push((start, &calendar[start..idx]));
+            }
+        } else if start.is_none() {
+            start = Some(idx);
+        }
+    }
+    if let Some(start) = start {
+        words.push((start, &calendar[start..]));
+    }
+    words
+}
+
+/// Validate the given calendar string
+///
+/// The calendar goes through the same parser as
+/// [`parse_calendar`](super::parse_calendar), and every problem it finds is
+/// reported along with the field and byte offset it was found at.  An empty
+/// result means the calendar is valid.
+#[must_use]
+pub fn validate_calendar(calendar: &str) -> Vec<CalendarDiagnostic> {
+    parse(calendar, Seed::default()).err().unwrap_or_default().0
+}
+
+#[cfg(test)]
+mod test {
+    use super::{validate_calendar, CalendarField};
+
+    fn reasons(calendar: &str) -> Vec<(CalendarField, usize, String)> {
+        validate_calendar(calendar)
+            .into_iter()
+            .map(|diag| (diag.field(), diag.offset(), diag.reason().clone()))
+            .collect()
+    }
+
+    #[test]
+    fn valid() {
+        assert!(validate_calendar("daily").is_empty());
+        assert!(validate_calendar("weekly UTC").is_empty());
+        assert!(validate_calendar("4:00:00").is_empty());
+        assert!(validate_calendar("*-02-29 12:00:00").is_empty());
+        assert!(validate_calendar("Mon..Fri *-*-* 0/2:R:R").is_empty());
+        assert!(
+            validate_calendar("Sat 2020..2030-01,03..09/2-1..7 4:00:00 Europe/Berlin").is_empty()
+        );
+        assert!(validate_calendar("Mon#1,Fri#L *-*~1..3 4:00:00").is_empty());
+        assert!(validate_calendar("*-*-~1 4:00:00").is_empty());
+    }
+
+    #[test]
+    fn impossible_date() {
+        assert_eq!(
+            reasons("*-02-30 00:00:00"),
+            vec![(
+                CalendarField::Day,
+                5,
+                "February never has 30 days".to_string()
+            )]
+        );
+        assert_eq!(
+            reasons("*-04,06-31 00:00:00"),
+            vec![(
+                CalendarField::Day,
+                8,
+                "none of the months ever has 31 days".to_string()
+            )]
+        );
+        assert!(validate_calendar("*-02,03-30 00:00:00").is_empty());
+    }
+
+    #[test]
+    fn out_of_range() {
+        assert_eq!(
+            reasons("*-*-* 99:00:00"),
+            vec![(
+                CalendarField::Hour,
+                6,
+                "hour 99 out of range 0..23".to_string()
+            )]
+        );
+        assert_eq!(
+            reasons("*-13-* 10:5..60:00"),
+            vec![
+                (
+                    CalendarField::Month,
+                    2,
+                    "month 13 out of range 1..12".to_string()
+                ),
+                (
+                    CalendarField::Minute,
+                    13,
+                    "minute 60 out of range 0..59".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn bad_parts() {
+        assert_eq!(
+            reasons("Mon..Foo *-*-0 1:2:3/0 Bogus/Zone"),
+            vec![
+                (
+                    CalendarField::Timezone,
+                    23,
+                    "unknown timezone 'Bogus/Zone'".to_string()
+                ),
+                (
+                    CalendarField::DayOfWeek,
+                    5,
+                    "unknown day of week 'Foo'".to_string()
+                ),
+                (
+                    CalendarField::Day,
+                    13,
+                    "day 0 out of range 1..31".to_string()
+                ),
+                (
+                    CalendarField::Second,
+                    21,
+                    "repetition '0' must be a number greater than 0".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            reasons("*-* 12:00"),
+            vec![
+                (
+                    CalendarField::Date,
+                    0,
+                    "expected a date like 'year-month-day', found '*-*'".to_string()
+                ),
+                (
+                    CalendarField::Time,
+                    4,
+                    "expected a time like 'hour:minute:second', found '12:00'".to_string()
+                ),
+            ]
+        );
+        assert_eq!(reasons("this is a bad calendar").len(), 1);
+        assert_eq!(
+            reasons("Mon#6 *-02~30 4:00:00"),
+            vec![
+                (
+                    CalendarField::DayOfWeek,
+                    4,
+                    "occurrence '6' must be 1..5 or L".to_string()
+                ),
+                (
+                    CalendarField::Day,
+                    11,
+                    "February never has 30 days".to_string()
+                ),
+            ]
+        );
+    }
+}
@@ -8,14 +8,14 @@
 
 // realtime yyyy-mm-dd helpers
 
-use super::{parse_time_chunk, All, RANGE_RE};
-use crate::error::Error::InvalidDate;
-use anyhow::{anyhow, Result};
-use rand::Rng;
+use super::{
+    parse_time_chunk, pick, split, All, CalendarDiagnostic, CalendarField, Diagnostics, Seed,
+};
+use time::{Date, Weekday};
 
 const MONTHS_PER_YEAR: u8 = 12;
-// TODO: Fix this
 const DAYS_PER_MONTH: u8 = 31;
+const MAX_YEAR: i32 = 9999;
 
 /// The year for a realtime schedule
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
@@ -55,6 +55,24 @@ impl Year {
             Year::Year(year) => *year == given,
         }
     }
+
+    pub(crate) fn next_match(&self, from: i32) -> Option<i32> {
+        match self {
+            Year::All => (from <= MAX_YEAR).then_some(from),
+            Year::Range(lo, hi) => (from <= *hi).then(|| from.max(*lo)),
+            Year::Repetition { start, end, rep } => {
+                let end = end.unwrap_or(MAX_YEAR);
+                let next = if from <= *start || *rep == 0 {
+                    *start
+                } else {
+                    let rep = i32::from(*rep);
+                    start + (from - start + rep - 1) / rep * rep
+                };
+                (from <= next && next <= end).then_some(next)
+            }
+            Year::Year(year) => (from <= *year).then_some(*year),
+        }
+    }
 }
 
 /// The month for a realtime schedule
@@ -73,6 +91,13 @@ impl Month {
             Month::Months(months) => months.contains(&given),
         }
     }
+
+    pub(crate) fn next_match(&self, from: u8) -> Option<u8> {
+        match self {
+            Month::All => (from <= MONTHS_PER_YEAR).then_some(from),
+            Month::Months(months) => months.iter().copied().filter(|x| *x >= from).min(),
+        }
+    }
 }
 
 impl All for Month {
@@ -80,10 +105,8 @@ impl All for Month {
         Self::All
     }
 
-    fn rand() -> Self {
-        let mut rng = rand::rng();
-        let rand_in_range = rng.random_range(1..13);
-        Month::Months(vec![rand_in_range])
+    fn rand(seed: u64) -> Self {
+        Month::Months(vec![pick(seed, MONTHS_PER_YEAR) + 1])
     }
 }
 
@@ -106,13 +129,45 @@ pub enum Day {
     All,
     /// Specific days
     Days(Vec<u8>),
+    /// Specific days counted back from the end of the month, where 1 is the
+    /// last day of the month
+    FromLast(Vec<u8>),
+    /// The weekday nearest the given day, without leaving the month
+    NearestWeekday(u8),
+    /// The last weekday of the month
+    LastWeekday,
 }
 
 impl Day {
-    pub(crate) fn matches(&self, given: u8) -> bool {
+    pub(crate) fn matches(&self, given: Date) -> bool {
         match self {
             Day::All => true,
-            Day::Days(days) => days.contains(&given),
+            Day::Days(days) => days.contains(&given.day()),
+            Day::FromLast(days) => {
+                let from_last = given.month().length(given.year()) - given.day() + 1;
+                days.contains(&from_last)
+            }
+            Day::NearestWeekday(day) => given.replace_day(*day).ok().is_some_and(|target| {
+                let last = target.month().length(target.year());
+                let nearest = match target.weekday() {
+                    Weekday::Saturday if *day == 1 => day + 2,
+                    Weekday::Saturday => day - 1,
+                    Weekday::Sunday if *day == last => day - 2,
+                    Weekday::Sunday => day + 1,
+                    _ => *day,
+                };
+                given.day() == nearest
+            }),
+            Day::LastWeekday => {
+                let last = given.month().length(given.year());
+                given
+                    .replace_day(last)
+                    .is_ok_and(|last_day| match last_day.weekday() {
+                        Weekday::Saturday => given.day() == last - 1,
+                        Weekday::Sunday => given.day() == last - 2,
+                        _ => given.day() == last,
+                    })
+            }
         }
     }
 }
@@ -122,10 +177,9 @@ impl All for Day {
         Self::All
     }
 
-    fn rand() -> Self {
-        let mut rng = rand::rng();
-        let rand_in_range = rng.random_range(1..29);
-        Day::Days(vec![rand_in_range])
+    fn rand(seed: u64) -> Self {
+        // every month has at least 28 days
+        Day::Days(vec![pick(seed, 28) + 1])
     }
 }
 
@@ -141,50 +195,125 @@ impl From<u8> for Day {
     }
 }
 
-pub(crate) fn parse_date(ymd: &str) -> Result<(Year, Month, Day)> {
-    let date_parts: Vec<&str> = ymd.split('-').collect();
-    if date_parts.len() == 3 {
-        let year = parse_year(date_parts[0])?;
-        let month = parse_time_chunk::<Month>(date_parts[1], MONTHS_PER_YEAR, true)?;
-        let day = parse_time_chunk::<Day>(date_parts[2], DAYS_PER_MONTH, true)?;
-        Ok((year, month, day))
-    } else {
-        Err(InvalidDate {
-            date: ymd.to_string(),
+pub(crate) fn parse_date(ymd: &str, seed: Seed) -> Result<(Year, Month, Day), Diagnostics> {
+    // 'year-month~day', or 'year-month-~day', counts the day back from the end
+    // of the month
+    let (parts, from_last) = match ymd.split_once('~') {
+        Some((ym, day)) => {
+            let mut parts = split(ym.strip_suffix('-').unwrap_or(ym), 0, '-');
+            parts.push((ymd.len() - day.len(), day));
+            (parts, true)
+        }
+        None => (split(ymd, 0, '-'), false),
+    };
+    let [(y_off, year), (m_off, month), (d_off, day)] = parts[..] else {
+        return Err(CalendarDiagnostic::new(
+            CalendarField::Date,
+            0,
+            format!("expected a date like 'year-month-day', found '{ymd}'"),
+        )
+        .into());
+    };
+    let random_month = month == "R";
+    let mut diagnostics = Diagnostics::default();
+    let year = diagnostics.check(y_off, parse_year(year));
+    let month = diagnostics.check(
+        m_off,
+        parse_time_chunk::<Month>(
+            month,
+            CalendarField::Month,
+            1,
+            MONTHS_PER_YEAR,
+            seed.field("month"),
+        ),
+    );
+    let day = diagnostics.check(
+        d_off,
+        parse_time_chunk::<Day>(
+            day,
+            CalendarField::Day,
+            1,
+            DAYS_PER_MONTH,
+            seed.field("day"),
+        ),
+    );
+    if let (Some(month), Some(day)) = (&month, &day) {
+        // a random month is only known once seeded, any month may be picked
+        if let Some(reason) = impossible_date(month, day).filter(|_| !random_month) {
+            diagnostics.push(CalendarField::Day, d_off, reason);
         }
-        .into())
     }
+    let parsed = year.zip(month).zip(day).map(|((year, month), day)| {
+        let day = match day {
+            Day::Days(days) if from_last => Day::FromLast(days),
+            day => day,
+        };
+        (year, month, day)
+    });
+    diagnostics.finish(parsed)
 }
 
-fn parse_year(yearish: &str) -> Result<Year> {
-    Ok(if yearish == "*" {
-        Year::All
-    } else if RANGE_RE.is_match(yearish) {
-        let caps = RANGE_RE.captures(yearish).ok_or_else(|| anyhow!(""))?;
-        let first = caps
-            .get(1)
-            .ok_or_else(|| anyhow!(""))?
-            .as_str()
-            .parse::<i32>()?;
-        let second = caps
-            .get(2)
-            .ok_or_else(|| anyhow!(""))?
-            .as_str()
-            .parse::<i32>()?;
-        Year::Range(first, second)
+fn parse_year(yearish: &str) -> Result<Year, CalendarDiagnostic> {
+    let invalid = |reason: String| CalendarDiagnostic::new(CalendarField::Year, 0, reason);
+    if yearish == "*" {
+        Ok(Year::All)
+    } else if let Some((first, second)) = yearish.split_once("..") {
+        match (first.parse::<i32>(), second.parse::<i32>()) {
+            (Ok(first), Ok(second)) if second < first => {
+                Err(invalid(format!("year range '{yearish}' is backwards")))
+            }
+            (Ok(first), Ok(second)) => Ok(Year::Range(first, second)),
+            _ => Err(invalid(format!("invalid year range '{yearish}'"))),
+        }
     } else {
-        Year::Year(yearish.parse::<i32>()?)
+        yearish
+            .parse::<i32>()
+            .map(Year::Year)
+            .map_err(|_| invalid(format!("invalid year '{yearish}'")))
+    }
+}
+
+// Why no month of the date has any of its days, if that is the case
+fn impossible_date(month: &Month, day: &Day) -> Option<String> {
+    let months = match month {
+        Month::All => (1..=MONTHS_PER_YEAR).collect(),
+        Month::Months(months) => months.clone(),
+    };
+    let days = match day {
+        Day::Days(days) | Day::FromLast(days) => days,
+        Day::All | Day::NearestWeekday(_) | Day::LastWeekday => return None,
+    };
+    let first_day = days.iter().min()?;
+    let possible = months
+        .iter()
+        .any(|month| days.iter().any(|day| *day <= max_days(*month)));
+    (!possible).then(|| match months[..] {
+        [month] => match time::Month::try_from(month) {
+            Ok(month) => format!("{month} never has {first_day} days"),
+            Err(_) => format!("month {month} never has {first_day} days"),
+        },
+        _ => format!("none of the months ever has {first_day} days"),
     })
 }
 
+// The most days the given month can have, allowing for leap years
+fn max_days(month: u8) -> u8 {
+    match month {
+        2 => 29,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use super::{parse_date, Day, Month, Year, DAYS_PER_MONTH, MONTHS_PER_YEAR};
+    use super::{parse_date, Day, Month, Seed, Year, DAYS_PER_MONTH, MONTHS_PER_YEAR};
     use anyhow::Result;
+    use time::macros::date;
 
     #[test]
     fn simple() -> Result<()> {
-        let (year, month, day) = parse_date("1976-03-22")?;
+        let (year, month, day) = parse_date("1976-03-22", Seed::default())?;
         assert_eq!(year, Year::Year(1976));
         assert_eq!(month, Month::Months(vec![3]));
         assert_eq!(day, Day::Days(vec![22]));
@@ -193,28 +322,30 @@ mod test {
 
     #[test]
     fn range() -> Result<()> {
-        let (year, month, day) = parse_date("1976-03..07-10..20")?;
+        let (year, month, day) = parse_date("1976-03..07-10..20", Seed::default())?;
         assert_eq!(year, Year::Year(1976));
         assert_eq!(month, Month::Months((3..=7).collect()));
         assert_eq!(day, Day::Days((10..=20).collect()));
+        let (year, _, _) = parse_date("2020..2030-*-*", Seed::default())?;
+        assert_eq!(year, Year::Range(2020, 2030));
         Ok(())
     }
 
     #[test]
     fn simple_repetition() -> Result<()> {
-        let (year, month, day) = parse_date("1976-01/2-01/3")?;
+        let (year, month, day) = parse_date("1976-01/2-01/3", Seed::default())?;
         assert_eq!(year, Year::Year(1976));
         assert_eq!(
             month,
-            Month::Months((1..MONTHS_PER_YEAR).step_by(2).collect())
+            Month::Months((1..=MONTHS_PER_YEAR).step_by(2).collect())
         );
-        assert_eq!(day, Day::Days((1..DAYS_PER_MONTH).step_by(3).collect()));
+        assert_eq!(day, Day::Days((1..=DAYS_PER_MONTH).step_by(3).collect()));
         Ok(())
     }
 
     #[test]
     fn range_repetition() -> Result<()> {
-        let (year, month, day) = parse_date("1976-03..09/2-10..20/3")?;
+        let (year, month, day) = parse_date("1976-03..09/2-10..20/3", Seed::default())?;
         assert_eq!(year, Year::Year(1976));
         assert_eq!(month, Month::Months((3..=9).step_by(2).collect()));
         assert_eq!(day, Day::Days((10..=20).step_by(3).collect()));
@@ -223,7 +354,7 @@ mod test {
 
     #[test]
     fn funky() -> Result<()> {
-        let (year, month, day) = parse_date("1976-01,03..09/2,10..12-10..20/3")?;
+        let (year, month, day) = parse_date("1976-01,03..09/2,10..12-10..20/3", Seed::default())?;
         assert_eq!(year, Year::Year(1976));
         assert_eq!(month, Month::Months(vec![1, 3, 5, 7, 9, 10, 11, 12]));
         assert_eq!(day, Day::Days((10..=20).step_by(3).collect()));
@@ -240,6 +371,20 @@ mod test {
         assert!(!years.matches(2025));
     }
 
+    #[test]
+    fn year_next_match_works() {
+        let years = Year::Repetition {
+            start: 2020,
+            end: Some(2030),
+            rep: 4,
+        };
+        assert_eq!(years.next_match(2000), Some(2020));
+        assert_eq!(years.next_match(2021), Some(2024));
+        assert_eq!(years.next_match(2024), Some(2024));
+        assert_eq!(years.next_match(2029), None);
+        assert_eq!(Year::Range(2022, 2024).next_match(2025), None);
+    }
+
     #[test]
     fn month_matching_works() {
         let months = Month::Months(vec![1, 3, 7]);
@@ -260,10 +405,53 @@ mod test {
     #[test]
     fn day_matching_works() {
         let days = Day::Days(vec![10, 11, 12]);
-        assert!(!days.matches(9));
-        assert!(days.matches(10));
-        assert!(days.matches(11));
-        assert!(days.matches(12));
-        assert!(!days.matches(13));
+        assert!(!days.matches(date!(2024 - 01 - 09)));
+        assert!(days.matches(date!(2024 - 01 - 10)));
+        assert!(days.matches(date!(2024 - 01 - 11)));
+        assert!(days.matches(date!(2024 - 01 - 12)));
+        assert!(!days.matches(date!(2024 - 01 - 13)));
+    }
+
+    #[test]
+    fn from_last() -> Result<()> {
+        let (_year, _month, day) = parse_date("*-*~1", Seed::default())?;
+        assert_eq!(day, Day::FromLast(vec![1]));
+        let (_year, month, day) = parse_date("*-02-~1..3", Seed::default())?;
+        assert_eq!(month, Month::Months(vec![2]));
+        assert_eq!(day, Day::FromLast(vec![1, 2, 3]));
+        Ok(())
+    }
+
+    #[test]
+    fn nearest_weekday_matching_works() {
+        // 2024-06-01 is a Saturday
+        let first = Day::NearestWeekday(1);
+        assert!(first.matches(date!(2024 - 06 - 03)));
+        assert!(!first.matches(date!(2024 - 06 - 01)));
+        // 2024-06-15 is a Saturday
+        let fifteenth = Day::NearestWeekday(15);
+        assert!(fifteenth.matches(date!(2024 - 06 - 14)));
+        // 2024-03-31 is a Sunday
+        let last = Day::NearestWeekday(31);
+        assert!(last.matches(date!(2024 - 03 - 29)));
+        assert!(!last.matches(date!(2024 - 04 - 30)));
+    }
+
+    #[test]
+    fn last_weekday_matching_works() {
+        // 2024-03-31 is a Sunday
+        assert!(Day::LastWeekday.matches(date!(2024 - 03 - 29)));
+        assert!(!Day::LastWeekday.matches(date!(2024 - 03 - 31)));
+        assert!(Day::LastWeekday.matches(date!(2024 - 04 - 30)));
+    }
+
+    #[test]
+    fn from_last_matching_works() {
+        let days = Day::FromLast(vec![1]);
+        assert!(days.matches(date!(2024 - 02 - 29)));
+        assert!(!days.matches(date!(2023 - 02 - 27)));
+        assert!(days.matches(date!(2023 - 02 - 28)));
+        assert!(days.matches(date!(2024 - 04 - 30)));
+        assert!(!days.matches(date!(2024 - 05 - 30)));
     }
 }
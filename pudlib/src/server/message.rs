@@ -8,7 +8,7 @@
 
 // Actix messages for a server
 
-use crate::{Command, JobDoc, Schedule};
+use crate::{Command, JobDoc, JobEvent, JobExit, OutputLine, RunningJob, Schedule};
 use actix::Message;
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashMap};
@@ -34,6 +34,33 @@ pub enum WorkerSessionToServer {
         name: String,
         /// The currently loaded schedules
         schedules: Vec<Schedule>,
+        /// The timezone the worker evaluates realtime schedules in when the
+        /// calendar does not name one
+        timezone: Option<String>,
+    },
+    /// Something happened to a job on a worker
+    JobEvent {
+        /// The manager that asked for this job to run now, if any
+        requested_by: Option<Uuid>,
+        /// The name of the worker
+        worker: String,
+        /// The command id associated with this job
+        id: Uuid,
+        /// The job name
+        name: String,
+        /// What happened
+        event: JobEvent,
+    },
+    /// The answer to a manager's request to cancel a job
+    CancelResult {
+        /// The manager that requested the cancel
+        manager_id: Uuid,
+        /// The name of the worker
+        worker: String,
+        /// The command id of the job to cancel
+        id: Uuid,
+        /// Was the job running, and is now being cancelled
+        cancelled: bool,
     },
 }
 
@@ -49,6 +76,26 @@ pub enum ServerToWorkerClient {
     Reload,
     /// A request for the current loaded schedules
     Schedules(Uuid),
+    /// Run a command now
+    Run {
+        /// The manager that asked for the command to run
+        manager_id: Uuid,
+        /// The name of the command to run
+        name: String,
+    },
+    /// Cancel a running job
+    Cancel {
+        /// The manager that asked for the job to be cancelled
+        manager_id: Uuid,
+        /// The command id of the job to cancel
+        id: Uuid,
+    },
+    /// The document of a job has been stored, so the worker can drop the
+    /// events it spooled for the job
+    JobStored {
+        /// The command id of the stored job
+        id: Uuid,
+    },
 }
 
 impl From<String> for ServerToWorkerClient {
@@ -86,6 +133,35 @@ pub enum ManagerSessionToServer {
         /// The job output
         output: Vec<JobDoc>,
     },
+    /// Run a command on a worker now
+    Run {
+        /// The id of the manager
+        id: Uuid,
+        /// The name of the worker to run the command on
+        worker: String,
+        /// The name of the command to run
+        command: String,
+    },
+    /// Follow the output of the jobs running on a worker
+    Tail {
+        /// The id of the manager
+        id: Uuid,
+        /// The name of the worker to follow
+        worker: String,
+        /// The job name to follow, or every job when not given
+        job: Option<String>,
+    },
+    /// List the jobs running on every worker
+    Running(Uuid),
+    /// Cancel a running job on a worker
+    Cancel {
+        /// The id of the manager
+        id: Uuid,
+        /// The name of the worker the job is running on
+        worker: String,
+        /// The command id of the job to cancel
+        job_id: Uuid,
+    },
 }
 
 /// A message for a manager
@@ -107,15 +183,24 @@ pub enum ServerToManagerClient {
         name: String,
         /// The schedules currently loaded on the worker
         schedules: Vec<Schedule>,
+        /// The timezone the worker evaluates realtime schedules in when the
+        /// calendar does not name one
+        timezone: Option<String>,
     },
     /// Job details
     QueryReturn {
-        /// The stdout from a job
-        stdout: Vec<String>,
-        /// The stderr from a job
-        stderr: Vec<String>,
-        /// The job status
-        status: i32,
+        /// The stdout and stderr from a job, in the order they were written
+        output: Vec<OutputLine>,
+        /// Was any of the output dropped by the output limits
+        truncated: bool,
+        /// The number of stdout lines dropped by the output limits
+        stdout_dropped: usize,
+        /// The number of stderr lines dropped by the output limits
+        stderr_dropped: usize,
+        /// The job status, if the job exited
+        status: Option<i32>,
+        /// How the job exited, if known
+        exit: Option<JobExit>,
         /// The start time of a job
         start_time: OffsetDateTime,
         /// The end time of a job
@@ -123,6 +208,30 @@ pub enum ServerToManagerClient {
         /// Are there any more messages coming?
         done: bool,
     },
+    /// A run request could not be started
+    RunFailed(String),
+    /// The jobs running on every worker
+    Running(Vec<RunningJob>),
+    /// The answer to a request to cancel a job
+    CancelResult {
+        /// The name of the worker
+        worker: String,
+        /// The command id of the job to cancel
+        id: Uuid,
+        /// Was the job running, and is now being cancelled
+        cancelled: bool,
+    },
+    /// Something happened to a job on a worker
+    JobEvent {
+        /// The name of the worker
+        worker: String,
+        /// The command id associated with this job
+        id: Uuid,
+        /// The job name
+        name: String,
+        /// What happened
+        event: JobEvent,
+    },
 }
 
 impl From<String> for ServerToManagerClient {
@@ -8,9 +8,11 @@
 
 // shared server code
 
+use crate::error::Error::InvalidCommand;
+use anyhow::Result;
 use getset::Getters;
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use std::{collections::BTreeMap, path::PathBuf, time::Duration};
 
 pub(crate) mod message;
 
@@ -18,8 +20,133 @@ pub(crate) mod message;
 #[derive(Clone, Debug, Default, Deserialize, Eq, Getters, PartialEq, Serialize)]
 #[getset(get = "pub")]
 pub struct Command {
-    /// The command to run
+    /// The command to run with a shell
+    #[serde(default)]
     cmd: String,
+    /// The program and its arguments to run directly, bypassing the shell
+    #[serde(default)]
+    argv: Vec<String>,
+    /// The shell used to run `cmd`, `$SHELL` or `/bin/sh` if not given
+    #[serde(default)]
+    shell: Option<String>,
+    /// Environment variables to set for the command
+    #[serde(default)]
+    env: BTreeMap<String, String>,
+    /// Start the command with an empty environment, rather than the worker's
+    #[serde(default)]
+    env_clear: bool,
+    /// The working directory for the command
+    #[serde(default)]
+    cwd: Option<PathBuf>,
+    /// The user to run the command as
+    #[serde(default)]
+    user: Option<String>,
+    /// The group to run the command as, the user's primary group if not given
+    #[serde(default)]
+    group: Option<String>,
+    /// The resource limits applied to the command
+    #[serde(default)]
+    limits: Limits,
+    /// How long the command may run before it is terminated
+    #[serde(default)]
+    timeout: Option<Duration>,
+    /// How long to wait after asking a timed out command to terminate before
+    /// it is killed
+    #[serde(default)]
+    grace_period: Option<Duration>,
+    /// How much of the command's output is kept in its job document
+    #[serde(default)]
+    output: OutputLimits,
+    /// How output lines that are not valid UTF-8 are captured
+    #[serde(default)]
+    encoding: OutputEncoding,
+}
+
+impl Command {
+    /// Check that the command has exactly one of `cmd` or `argv`, and that its
+    /// limits are valid
+    ///
+    /// # Errors
+    ///
+    pub fn validate(&self) -> Result<()> {
+        match (self.cmd.is_empty(), self.argv.is_empty()) {
+            (true, true) => Err(InvalidCommand {
+                reason: "one of 'cmd' or 'argv' is required".to_string(),
+            }
+            .into()),
+            (false, false) => Err(InvalidCommand {
+                reason: "only one of 'cmd' or 'argv' may be given".to_string(),
+            }
+            .into()),
+            _ => self.limits.validate(),
+        }
+    }
+}
+
+/// Resource limits for a command
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Getters, PartialEq, Serialize)]
+#[getset(get = "pub")]
+pub struct Limits {
+    /// The CPU time limit in seconds
+    #[serde(default)]
+    cpu_secs: Option<u64>,
+    /// The address space limit in bytes
+    #[serde(default)]
+    address_space: Option<u64>,
+    /// The maximum number of open files
+    #[serde(default)]
+    open_files: Option<u64>,
+    /// The nice level, from -20 (highest priority) to 19 (lowest)
+    #[serde(default)]
+    nice: Option<i32>,
+}
+
+impl Limits {
+    fn validate(&self) -> Result<()> {
+        match self.nice {
+            Some(nice) if !(-20..=19).contains(&nice) => Err(InvalidCommand {
+                reason: format!("nice level {nice} is not between -20 and 19"),
+            }
+            .into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// How much of each output stream of a command is kept in its job document.
+/// When either line limit is given, the first `head_lines` and the last
+/// `tail_lines` lines are kept and the lines between are dropped.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Getters, PartialEq, Serialize)]
+#[getset(get = "pub")]
+pub struct OutputLimits {
+    /// The number of lines kept from the start of the stream
+    #[serde(default)]
+    head_lines: Option<usize>,
+    /// The number of lines kept from the end of the stream
+    #[serde(default)]
+    tail_lines: Option<usize>,
+    /// The number of bytes kept, shared between the head and the tail
+    #[serde(default)]
+    max_bytes: Option<usize>,
+}
+
+impl OutputLimits {
+    /// Is any of the output going to be dropped
+    #[must_use]
+    pub fn is_limited(&self) -> bool {
+        self.head_lines.is_some() || self.tail_lines.is_some() || self.max_bytes.is_some()
+    }
+}
+
+/// How an output line that is not valid UTF-8 is captured
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputEncoding {
+    /// Replace the invalid bytes with U+FFFD
+    #[default]
+    Lossy,
+    /// Keep the line as it was written, base64 encoded
+    Base64,
 }
 
 /// The schedule to run commands on a given worker client
@@ -49,6 +176,9 @@ pub enum Schedule {
         on_unit_active_sec: Duration,
         /// The commands to run
         cmds: Vec<String>,
+        /// What to do when the schedule fires while its previous run is still going
+        #[serde(default)]
+        overlap: Overlap,
     },
     /// A realtime schedule
     Realtime {
@@ -58,19 +188,69 @@ pub enum Schedule {
         persistent: bool,
         /// The commands to run
         cmds: Vec<String>,
+        /// What to do when the schedule fires while its previous run is still going
+        #[serde(default)]
+        overlap: Overlap,
+    },
+    /// A cron schedule
+    Cron {
+        /// A five or six field cron expression, or an alias such as `@reboot`
+        expr: String,
+        /// The commands to run
+        cmds: Vec<String>,
+        /// What to do when the schedule fires while its previous run is still going
+        #[serde(default)]
+        overlap: Overlap,
     },
 }
 
+/// What a schedule does when it fires while its previous run is still going
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Overlap {
+    /// Start another run alongside the previous one
+    #[default]
+    Allow,
+    /// Don't run, and report the fire as skipped
+    Skip,
+    /// Run once more after the previous run finishes
+    Queue,
+    /// Kill the previous run and start a new one
+    Replace,
+}
+
 #[cfg(test)]
 mod test {
-    use super::{Schedule, Schedules};
+    use super::{Command, OutputEncoding, Overlap, Schedule, Schedules};
     use anyhow::Result;
+    use std::{path::PathBuf, time::Duration};
     use toml::from_str;
 
+    const COMMAND: &str = r#"cmd = "rustup update"
+timeout = { secs = 600, nanos = 0 }
+"#;
+
+    const ARGV_COMMAND: &str = r#"argv = ["cargo", "build", "--release"]
+env = { RUST_LOG = "info" }
+env_clear = true
+cwd = "/home/pud/project"
+"#;
+
+    const LIMITED_COMMAND: &str = r#"cmd = "cargo install-update -a"
+user = "pud"
+limits = { cpu_secs = 3600, address_space = 1073741824, nice = 10 }
+"#;
+
+    const CHATTY_COMMAND: &str = r#"cmd = "cargo build -vv"
+output = { head_lines = 100, tail_lines = 200, max_bytes = 65536 }
+encoding = "base64"
+"#;
+
     const SCHEDULES: &str = r#"schedules = [ 
     { Realtime = { on_calendar = "*-*-* 4:00:00", persistent = false, cmds = ["python"] } },
     { Realtime = { on_calendar = "*-*-* 4:30:00", persistent = false, cmds = ["tmux"] } },
-    { Monotonic = { on_boot_sec = { secs = 1, nanos = 0 }, on_unit_active_sec = { secs = 1, nanos = 0 }, cmds = ["updall"] } } 
+    { Monotonic = { on_boot_sec = { secs = 1, nanos = 0 }, on_unit_active_sec = { secs = 1, nanos = 0 }, cmds = ["updall"] } },
+    { Cron = { expr = "*/5 * * * *", cmds = ["uptime"], overlap = "skip" } }
 ]"#;
 
     #[test]
@@ -84,11 +264,18 @@ mod test {
                     on_calendar: _,
                     persistent: _,
                     cmds: _,
+                    overlap: _,
                 } => true,
                 Schedule::Monotonic {
                     on_boot_sec: _,
                     on_unit_active_sec: _,
                     cmds: _,
+                    overlap: _,
+                }
+                | Schedule::Cron {
+                    expr: _,
+                    cmds: _,
+                    overlap: _,
                 } => false,
             })
             .cloned();
@@ -100,17 +287,134 @@ mod test {
                     on_boot_sec: _,
                     on_unit_active_sec: _,
                     cmds: _,
+                    overlap: _,
                 } => true,
                 Schedule::Realtime {
                     on_calendar: _,
                     persistent: _,
                     cmds: _,
+                    overlap: _,
+                }
+                | Schedule::Cron {
+                    expr: _,
+                    cmds: _,
+                    overlap: _,
                 } => false,
             })
             .cloned();
-        assert_eq!(3, schedules.schedules().len());
+        let cron = schedules
+            .schedules()
+            .iter()
+            .filter(|x| {
+                matches!(
+                    x,
+                    Schedule::Cron {
+                        expr: _,
+                        cmds: _,
+                        overlap: _,
+                    }
+                )
+            })
+            .cloned();
+        assert_eq!(4, schedules.schedules().len());
         assert_eq!(2, realtime.count());
         assert_eq!(1, monotonic.count());
+        assert_eq!(1, cron.count());
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_overlap() -> Result<()> {
+        let schedules: Schedules = from_str(SCHEDULES)?;
+        let overlaps: Vec<Overlap> = schedules
+            .schedules()
+            .iter()
+            .map(|x| match x {
+                Schedule::Monotonic { overlap, .. }
+                | Schedule::Realtime { overlap, .. }
+                | Schedule::Cron { overlap, .. } => *overlap,
+            })
+            .collect();
+        assert_eq!(
+            overlaps,
+            vec![
+                Overlap::Allow,
+                Overlap::Allow,
+                Overlap::Allow,
+                Overlap::Skip
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_command() -> Result<()> {
+        let command: Command = from_str(COMMAND)?;
+        assert_eq!(command.cmd(), "rustup update");
+        assert_eq!(*command.timeout(), Some(Duration::from_secs(600)));
+        assert!(command.grace_period().is_none());
+        let command: Command = from_str(r#"cmd = "uname -a""#)?;
+        assert!(command.timeout().is_none());
+        assert!(command.validate().is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_command_environment() -> Result<()> {
+        let command: Command = from_str(ARGV_COMMAND)?;
+        assert!(command.cmd().is_empty());
+        assert_eq!(command.argv(), &["cargo", "build", "--release"]);
+        assert_eq!(
+            command.env().get("RUST_LOG").map(String::as_str),
+            Some("info")
+        );
+        assert!(*command.env_clear());
+        assert_eq!(command.cwd(), &Some(PathBuf::from("/home/pud/project")));
+        assert!(command.shell().is_none());
+        assert!(command.validate().is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn validate_command() -> Result<()> {
+        let command: Command = from_str(r#"shell = "/bin/bash""#)?;
+        assert!(command.validate().is_err());
+        let command: Command = from_str(
+            r#"cmd = "uname -a"
+argv = ["uname", "-a"]"#,
+        )?;
+        assert!(command.validate().is_err());
+        let command: Command = from_str(
+            r#"cmd = "uname -a"
+limits = { nice = 20 }"#,
+        )?;
+        assert!(command.validate().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_command_limits() -> Result<()> {
+        let command: Command = from_str(LIMITED_COMMAND)?;
+        assert_eq!(command.user().as_deref(), Some("pud"));
+        assert!(command.group().is_none());
+        assert_eq!(*command.limits().cpu_secs(), Some(3600));
+        assert_eq!(*command.limits().address_space(), Some(1_073_741_824));
+        assert!(command.limits().open_files().is_none());
+        assert_eq!(*command.limits().nice(), Some(10));
+        assert!(command.validate().is_ok());
+        assert!(!command.output().is_limited());
+        assert_eq!(*command.encoding(), OutputEncoding::Lossy);
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_output_limits() -> Result<()> {
+        let command: Command = from_str(CHATTY_COMMAND)?;
+        assert!(command.output().is_limited());
+        assert_eq!(*command.output().head_lines(), Some(100));
+        assert_eq!(*command.output().tail_lines(), Some(200));
+        assert_eq!(*command.output().max_bytes(), Some(65536));
+        assert_eq!(*command.encoding(), OutputEncoding::Base64);
         Ok(())
     }
 }
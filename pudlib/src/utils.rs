@@ -8,7 +8,6 @@
 
 // Utilities
 
-use anyhow::Result;
 use bytes::Bytes;
 use std::time::{Duration, Instant};
 
@@ -35,14 +34,3 @@ pub fn send_ts_ping(origin: Instant) -> [u8; 12] {
     ts[8..12].copy_from_slice(&ts2.to_be_bytes());
     ts
 }
-
-#[allow(clippy::mut_mut)]
-pub(crate) fn until_err<T>(err: &mut &mut Result<()>, item: Result<T>) -> Option<T> {
-    match item {
-        Ok(item) => Some(item),
-        Err(e) => {
-            **err = Err(e);
-            None
-        }
-    }
-}
@@ -8,9 +8,11 @@
 
 //! Worker Actix Message
 
-use crate::Schedule;
+use crate::{JobExit, KillReason, OutputLimits, Schedule};
 use actix::Message;
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use time::OffsetDateTime;
 use uuid::Uuid;
 
 /// A message from a worker client to a worker session
@@ -25,6 +27,12 @@ pub enum WorkerClientToWorkerSession {
         id: Uuid,
         /// The job name
         name: String,
+        /// The manager that asked for this job to run now, if any
+        requested_by: Option<Uuid>,
+        /// How much of the job's output to keep
+        output: OutputLimits,
+        /// When the job started
+        start_time: OffsetDateTime,
     },
     /// A job has ended on the worker
     JobEnd {
@@ -32,27 +40,69 @@ pub enum WorkerClientToWorkerSession {
         id: Uuid,
         /// The job name
         name: String,
+        /// When the job ended
+        end_time: OffsetDateTime,
     },
     /// A stdout line from a command
     Stdout {
         /// The command id associated with this line
         id: Uuid,
+        /// How long after the job started the line was written
+        offset: Duration,
         /// The stdout line
         line: String,
+        /// Is the line base64 encoded, because it was not valid UTF-8
+        base64: bool,
     },
     /// A stderr line from a command
     Stderr {
         /// The command id associated with this line
         id: Uuid,
+        /// How long after the job started the line was written
+        offset: Duration,
         /// The stderr line
         line: String,
+        /// Is the line base64 encoded, because it was not valid UTF-8
+        base64: bool,
     },
-    /// A status from a command
-    Status {
-        /// The command id associated with this status
+    /// How a command exited
+    Exit {
+        /// The command id associated with this exit
         id: Uuid,
-        /// The status code
-        code: i32,
+        /// The exit details
+        exit: JobExit,
+    },
+    /// A command was killed by the worker
+    Killed {
+        /// The command id associated with this job
+        id: Uuid,
+        /// Why the command was killed
+        reason: KillReason,
+    },
+    /// A command was cancelled by a manager and has been killed
+    Cancelled {
+        /// The command id associated with this job
+        id: Uuid,
+        /// The manager that cancelled the job
+        manager_id: Uuid,
+    },
+    /// The answer to a manager's request to cancel a job
+    CancelResult {
+        /// The manager that requested the cancel
+        manager_id: Uuid,
+        /// The command id of the job to cancel
+        id: Uuid,
+        /// Was the job running, and is now being cancelled
+        cancelled: bool,
+    },
+    /// A job was skipped because the previous run of its schedule was still going
+    Skipped {
+        /// The command id associated with this job
+        id: Uuid,
+        /// The job name
+        name: String,
+        /// When the job was skipped
+        start_time: OffsetDateTime,
     },
     /// An initialization request from a worker
     Initialize,
@@ -62,6 +112,9 @@ pub enum WorkerClientToWorkerSession {
         manager_id: Uuid,
         /// The currently loaded schedules
         schedules: Vec<Schedule>,
+        /// The timezone the worker evaluates realtime schedules in when the
+        /// calendar does not name one
+        timezone: Option<String>,
     },
 }
 
@@ -73,7 +126,9 @@ impl WorkerClientToWorkerSession {
     {
         Self::Stdout {
             id: Uuid::new_v4(),
+            offset: Duration::ZERO,
             line: value.into(),
+            base64: false,
         }
     }
 
@@ -84,7 +139,9 @@ impl WorkerClientToWorkerSession {
     {
         Self::Stderr {
             id: Uuid::new_v4(),
+            offset: Duration::ZERO,
             line: value.into(),
+            base64: false,
         }
     }
 }
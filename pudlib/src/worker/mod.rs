@@ -8,4 +8,208 @@
 
 //! Worker
 
+use getset::{CopyGetters, Getters, Setters};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Display, time::Duration};
+use time::OffsetDateTime;
+
 pub(crate) mod message;
+
+/// The reason a worker killed a running job
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KillReason {
+    /// The job ran longer than its command timeout
+    Timeout,
+    /// The worker schedules were stopped, e.g. for a reload
+    Stopped,
+    /// The job was replaced by a newer run of the same schedule
+    Replaced,
+    /// The job was cancelled by a manager
+    Cancelled,
+    /// The worker stopped while the job was running, so how it ended is unknown
+    Lost,
+}
+
+/// How a job's process exited
+#[derive(Clone, Copy, CopyGetters, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[getset(get_copy = "pub")]
+pub struct JobExit {
+    /// The exit code, if the process exited normally
+    code: Option<i32>,
+    /// The signal that terminated the process, if any
+    signal: Option<i32>,
+    /// Did the process dump core
+    core_dumped: bool,
+    /// Was the process killed by the worker
+    killed: bool,
+}
+
+impl JobExit {
+    /// Create a new job exit
+    #[must_use]
+    pub fn new(code: Option<i32>, signal: Option<i32>, core_dumped: bool, killed: bool) -> Self {
+        Self {
+            code,
+            signal,
+            core_dumped,
+            killed,
+        }
+    }
+
+    /// Did the job exit normally with a zero exit code
+    #[must_use]
+    pub fn success(&self) -> bool {
+        self.code == Some(0) && self.signal.is_none()
+    }
+
+    /// A single status code for the exit, following the shell convention of
+    /// 128 plus the signal number for jobs terminated by a signal.  This is
+    /// -1 if neither an exit code nor a signal are known.
+    #[must_use]
+    pub fn status(&self) -> i32 {
+        match (self.code, self.signal) {
+            (Some(code), _) => code,
+            (None, Some(signal)) => 128 + signal,
+            (None, None) => -1,
+        }
+    }
+}
+
+impl Display for JobExit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.code, self.signal) {
+            (Some(code), _) => write!(f, "exited with code {code}")?,
+            (None, Some(signal)) => write!(f, "terminated by signal {signal}")?,
+            (None, None) => write!(f, "exited with an unknown status")?,
+        }
+        if self.core_dumped {
+            write!(f, " (core dumped)")?;
+        }
+        if self.killed {
+            write!(f, ", killed by the worker")?;
+        }
+        Ok(())
+    }
+}
+
+/// The output stream of a job
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stream {
+    /// Standard output
+    Stdout,
+    /// Standard error
+    Stderr,
+}
+
+impl Display for Stream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Stdout => write!(f, "out"),
+            Self::Stderr => write!(f, "err"),
+        }
+    }
+}
+
+/// A line of job output, with when it was written and to which stream
+#[derive(Clone, CopyGetters, Debug, Deserialize, Eq, Getters, PartialEq, Serialize, Setters)]
+pub struct OutputLine {
+    /// How long after the job started the line was written
+    #[getset(get_copy = "pub")]
+    offset: Duration,
+    /// The stream the line was written to
+    #[getset(get_copy = "pub")]
+    stream: Stream,
+    /// The line
+    #[getset(get = "pub")]
+    line: String,
+    /// Is the line base64 encoded, because it was not valid UTF-8
+    #[serde(default)]
+    #[getset(get_copy = "pub", set = "pub")]
+    base64: bool,
+}
+
+impl OutputLine {
+    /// Create a new output line
+    #[must_use]
+    pub fn new<T>(offset: Duration, stream: Stream, line: T) -> Self
+    where
+        T: Into<String>,
+    {
+        Self {
+            offset,
+            stream,
+            line: line.into(),
+            base64: false,
+        }
+    }
+}
+
+impl Display for OutputLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "+{:>10.3}s {} | {}{}",
+            self.offset.as_secs_f64(),
+            self.stream,
+            if self.base64 { "[base64] " } else { "" },
+            self.line
+        )
+    }
+}
+
+/// Something that happened to a running job, as streamed to a manager
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum JobEvent {
+    /// The job has started, at the given time
+    Started(OffsetDateTime),
+    /// A line of output from the job
+    Output(OutputLine),
+    /// How the job exited
+    Exit(JobExit),
+    /// The job has ended
+    Ended,
+}
+
+#[cfg(test)]
+mod test {
+    use super::{JobExit, OutputLine, Stream};
+    use std::time::Duration;
+
+    #[test]
+    fn exit_code() {
+        let exit = JobExit::new(Some(0), None, false, false);
+        assert!(exit.success());
+        assert_eq!(exit.status(), 0);
+        assert_eq!(exit.to_string(), "exited with code 0");
+        let exit = JobExit::new(Some(2), None, false, false);
+        assert!(!exit.success());
+        assert_eq!(exit.status(), 2);
+    }
+
+    #[test]
+    fn signal_is_never_success() {
+        let exit = JobExit::new(None, Some(9), false, true);
+        assert!(!exit.success());
+        assert_eq!(exit.status(), 137);
+        assert_eq!(
+            exit.to_string(),
+            "terminated by signal 9, killed by the worker"
+        );
+        let exit = JobExit::new(None, Some(11), true, false);
+        assert!(!exit.success());
+        assert_eq!(exit.status(), 139);
+        assert_eq!(exit.to_string(), "terminated by signal 11 (core dumped)");
+        assert_eq!(JobExit::new(None, None, false, false).status(), -1);
+    }
+
+    #[test]
+    fn output_line_display() {
+        let line = OutputLine::new(Duration::from_millis(1_500), Stream::Stderr, "oops");
+        assert_eq!(line.to_string(), "+     1.500s err | oops");
+        let mut line = OutputLine::new(Duration::ZERO, Stream::Stdout, "/w==");
+        _ = line.set_base64(true);
+        assert_eq!(line.to_string(), "+     0.000s out | [base64] /w==");
+    }
+}
@@ -8,5 +8,17 @@
 
 // Constants
 
+use std::time::Duration;
+
+/// How often the job store is pruned, when a retention policy is configured
+pub(crate) const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// The directory the file job store uses when none is configured
+pub(crate) const DEFAULT_STORE_PATH: &str = "jobs";
 #[cfg(test)]
 pub(crate) const TEST_PATH: &str = "test/config.toml";
+#[cfg(test)]
+pub(crate) const INVALID_CALENDAR_PATH: &str = "test/invalid_calendar.toml";
+#[cfg(test)]
+pub(crate) const INVALID_COMMAND_PATH: &str = "test/invalid_command.toml";
+#[cfg(test)]
+pub(crate) const CONFLICTING_STORES_PATH: &str = "test/conflicting_stores.toml";
@@ -9,14 +9,13 @@
 //! Insecure Manager websocket endpoint
 
 use super::Name;
-use crate::{error::Error::Actix, manager::session::Session, server::Server};
+use crate::{error::Error::Actix, manager::session::Session, server::Server, store::JobStore};
 use actix::Addr;
 use actix_web::{
     web::{Data, Json, Payload, Query},
     HttpRequest, HttpResponse,
 };
 use actix_web_actors::ws::start;
-use ruarango::Connection;
 use std::time::Instant;
 use tracing::{debug, error, info};
 use uuid::Uuid;
@@ -28,7 +27,7 @@ pub(crate) async fn manager(
     stream: Payload,
     name: Query<Name>,
     srv: Data<Addr<Server>>,
-    conn: Data<Connection>,
+    store: Data<dyn JobStore>,
 ) -> HttpResponse {
     info!("manager connecting...");
     let unknown = String::from("Unknown");
@@ -46,7 +45,7 @@ pub(crate) async fn manager(
             .ip(ip)
             .hb(Instant::now())
             .origin(Instant::now())
-            .conn(conn.as_ref().clone())
+            .store(store.into_inner())
             .build(),
         &request,
         stream,
@@ -26,6 +26,29 @@ pub(crate) enum Error {
         source: AddrParseError,
         addr: String,
     },
+    #[error("only one of the 'arangodb' and 'storage' job stores can be configured")]
+    ConflictingStores,
+    #[cfg(not(feature = "arangodb"))]
+    #[error("puds was built without support for the '{store}' job store")]
+    UnsupportedStore { store: String },
+    #[error("invalid calendar '{calendar}' for '{worker}': {diagnostics}")]
+    InvalidCalendar {
+        worker: String,
+        calendar: String,
+        diagnostics: String,
+    },
+    #[error("invalid command '{name}'")]
+    InvalidCommand {
+        #[source]
+        source: anyhow::Error,
+        name: String,
+    },
+    #[error("invalid cron schedule for '{worker}'")]
+    InvalidCron {
+        #[source]
+        source: anyhow::Error,
+        worker: String,
+    },
 }
 
 impl Serialize for Error {
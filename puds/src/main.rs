@@ -249,6 +249,7 @@ mod manager;
 mod model;
 mod runtime;
 mod server;
+mod store;
 mod utils;
 mod worker;
 
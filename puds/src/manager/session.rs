@@ -11,6 +11,7 @@
 use crate::{
     manager::message::{Connect, Disconnect},
     server::Server,
+    store::JobStore,
     utils::handle_server_to_client,
 };
 use actix::{
@@ -23,11 +24,13 @@ use actix_web_actors::ws::{Message, ProtocolError, WebsocketContext};
 use bincode::deserialize;
 use bytestring::ByteString;
 use pudlib::{
-    parse_ts_ping, send_ts_ping, JobDoc, ManagerClientToManagerSession, ManagerSessionToServer,
+    parse_ts_ping, send_ts_ping, ManagerClientToManagerSession, ManagerSessionToServer,
     ServerToManagerClient,
 };
-use ruarango::{cursor::input::CreateConfigBuilder, Connection, Cursor};
-use std::time::{Duration, Instant};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tracing::{debug, error, info};
 use typed_builder::TypedBuilder;
 use uuid::Uuid;
@@ -53,8 +56,8 @@ pub(crate) struct Session {
     /// continuation bytes
     #[builder(default = BytesMut::new())]
     cont_bytes: BytesMut,
-    /// A connection to the database
-    conn: Connection,
+    /// The job store
+    store: Arc<dyn JobStore>,
     /// The start instant of this session
     origin: Instant,
 }
@@ -132,40 +135,55 @@ impl Session {
                     self.addr
                         .do_send(ManagerSessionToServer::Schedules { id: self.id, name });
                 }
+                ManagerClientToManagerSession::Run { worker, command } => {
+                    self.addr.do_send(ManagerSessionToServer::Run {
+                        id: self.id,
+                        worker,
+                        command,
+                    });
+                }
+                ManagerClientToManagerSession::Tail { worker, job } => {
+                    self.addr.do_send(ManagerSessionToServer::Tail {
+                        id: self.id,
+                        worker,
+                        job,
+                    });
+                }
+                ManagerClientToManagerSession::Running => {
+                    self.addr.do_send(ManagerSessionToServer::Running(self.id));
+                }
+                ManagerClientToManagerSession::Cancel { worker, id } => {
+                    self.addr.do_send(ManagerSessionToServer::Cancel {
+                        id: self.id,
+                        worker,
+                        job_id: id,
+                    });
+                }
                 ManagerClientToManagerSession::Query(query) => {
-                    if let Ok(config) = CreateConfigBuilder::default()
-                        .query(query)
-                        .count(true)
-                        .build()
-                    {
-                        let conn_c = self.conn.clone();
-                        let id_c = self.id;
-                        let addr_c = self.addr.clone();
-                        let _handle = ctx.spawn(
-                            async move {
-                                match Cursor::create::<JobDoc>(&conn_c, config).await {
-                                    Ok(res) => {
-                                        if let Ok(meta) = res.right_safe() {
-                                            if let Some(job_doc) = meta.result() {
-                                                addr_c.do_send(ManagerSessionToServer::Query {
-                                                    id: id_c,
-                                                    output: job_doc.clone(),
-                                                });
-                                            } else {
-                                                error!("no cursor meta result");
-                                            }
-                                        } else {
-                                            error!("no cursor meta");
-                                        }
-                                    }
-                                    Err(e) => error!("{e}"),
+                    let query_jobs = self.store.query_jobs(&query);
+                    let id_c = self.id;
+                    let addr_c = self.addr.clone();
+                    let _handle = ctx.spawn(
+                        async move {
+                            match query_jobs.await {
+                                Ok(output) => {
+                                    addr_c.do_send(ManagerSessionToServer::Query {
+                                        id: id_c,
+                                        output,
+                                    });
+                                }
+                                Err(e) => {
+                                    error!("{e}");
+                                    // let the manager know there is nothing coming
+                                    addr_c.do_send(ManagerSessionToServer::Query {
+                                        id: id_c,
+                                        output: vec![],
+                                    });
                                 }
                             }
-                            .into_actor(self),
-                        );
-                    } else {
-                        error!("unable to build cursor query");
-                    }
+                        }
+                        .into_actor(self),
+                    );
                 }
             },
             Err(e) => error!("{e}"),
@@ -8,9 +8,16 @@
 
 // Configuration Models
 
-use crate::error::Error::{self, AddrParse};
+#[cfg(not(feature = "arangodb"))]
+use crate::error::Error::UnsupportedStore;
+use crate::{
+    constants::DEFAULT_STORE_PATH,
+    error::Error::{
+        self, AddrParse, ConflictingStores, InvalidCalendar, InvalidCommand, InvalidCron,
+    },
+};
 use getset::{Getters, Setters};
-use pudlib::{Command, LogConfig, Schedules, Verbosity};
+use pudlib::{parse_cron, validate_calendar, Command, LogConfig, Schedule, Schedules, Verbosity};
 use serde::{Deserialize, Serialize};
 use std::{
     collections::BTreeMap,
@@ -42,13 +49,33 @@ pub(crate) struct Config {
     default: BTreeMap<String, Command>,
     overrides: BTreeMap<String, BTreeMap<String, Command>>,
     schedules: BTreeMap<String, Schedules>,
-    db_url: String,
-    db_user: String,
-    db_pass: String,
-    db_name: String,
+    store: Store,
+    retention: Option<Retention>,
     with_level: bool,
 }
 
+/// Where the job documents are kept
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Store {
+    /// JSON lines files in a local directory
+    File {
+        /// The directory holding the files
+        path: PathBuf,
+    },
+    /// An `ArangoDB` database
+    #[cfg(feature = "arangodb")]
+    Arangodb {
+        /// The `ArangoDB` url
+        url: String,
+        /// The user
+        user: String,
+        /// The password
+        password: String,
+        /// The database name
+        name: String,
+    },
+}
+
 impl Verbosity for Config {
     fn set_quiet(&mut self, quiet: u8) -> &mut Self {
         self.quiet = quiet;
@@ -116,10 +143,8 @@ impl TryFrom<TomlConfig> for Config {
             source: e,
             addr: ip.clone(),
         })?;
-        let db_url = config.arangodb().url().clone();
-        let db_user = config.arangodb().user().clone();
-        let db_pass = config.arangodb().password().clone();
-        let db_name = config.arangodb().name().clone();
+        let store = store(config.arangodb().as_ref(), config.storage().as_ref())?;
+        let retention = config.retention().clone();
 
         let (target, thread_id, thread_names, line_numbers, with_level) =
             if let Some(tracing) = config.tracing() {
@@ -134,6 +159,8 @@ impl TryFrom<TomlConfig> for Config {
                 (false, false, false, false, true)
             };
         let socket_addr = SocketAddr::from((ip_addr, *port));
+        validate_commands(config.default(), config.overrides())?;
+        validate_schedules(config.schedules())?;
         let (tls, hostlist, default, overrides, schedules) = config.take();
         let (cert_file_path, key_file_path) = tls.take();
         Ok(Config {
@@ -153,15 +180,83 @@ impl TryFrom<TomlConfig> for Config {
             default,
             overrides,
             schedules,
-            db_url,
-            db_user,
-            db_pass,
-            db_name,
+            store,
+            retention,
             with_level,
         })
     }
 }
 
+// At most one job store can be configured, the file store is used when none is
+fn store(arangodb: Option<&Arangodb>, storage: Option<&Storage>) -> Result<Store, Error> {
+    match (arangodb, storage) {
+        (Some(_), Some(_)) => Err(ConflictingStores),
+        #[cfg(feature = "arangodb")]
+        (Some(arangodb), None) => Ok(Store::Arangodb {
+            url: arangodb.url.clone(),
+            user: arangodb.user.clone(),
+            password: arangodb.password.clone(),
+            name: arangodb.name.clone(),
+        }),
+        #[cfg(not(feature = "arangodb"))]
+        (Some(_), None) => Err(UnsupportedStore {
+            store: "arangodb".to_string(),
+        }),
+        (None, Some(storage)) => Ok(Store::File {
+            path: storage.path.clone(),
+        }),
+        (None, None) => Ok(Store::File {
+            path: PathBuf::from(DEFAULT_STORE_PATH),
+        }),
+    }
+}
+
+// Refuse to load any command that doesn't say what to run
+fn validate_commands(
+    default: &BTreeMap<String, Command>,
+    overrides: &BTreeMap<String, BTreeMap<String, Command>>,
+) -> Result<(), Error> {
+    for (name, command) in default.iter().chain(overrides.values().flatten()) {
+        command.validate().map_err(|source| InvalidCommand {
+            source,
+            name: name.clone(),
+        })?;
+    }
+    Ok(())
+}
+
+// Refuse to load any realtime or cron schedule that would never run as written
+fn validate_schedules(schedules: &BTreeMap<String, Schedules>) -> Result<(), Error> {
+    for (worker, schedules) in schedules {
+        for schedule in schedules.schedules() {
+            match schedule {
+                Schedule::Realtime { on_calendar, .. } => {
+                    let diagnostics = validate_calendar(on_calendar);
+                    if !diagnostics.is_empty() {
+                        return Err(InvalidCalendar {
+                            worker: worker.clone(),
+                            calendar: on_calendar.clone(),
+                            diagnostics: diagnostics
+                                .iter()
+                                .map(ToString::to_string)
+                                .collect::<Vec<String>>()
+                                .join(", "),
+                        });
+                    }
+                }
+                Schedule::Cron { expr, .. } => {
+                    _ = parse_cron(expr).map_err(|source| InvalidCron {
+                        source,
+                        worker: worker.clone(),
+                    })?;
+                }
+                Schedule::Monotonic { .. } => {}
+            }
+        }
+    }
+    Ok(())
+}
+
 /// The TOML configuration.
 #[derive(Clone, Debug, Default, Deserialize, Eq, Getters, PartialEq, Serialize)]
 #[getset(get = "pub(crate)")]
@@ -170,8 +265,12 @@ pub(crate) struct TomlConfig {
     actix: Actix,
     /// The TLS configuration
     tls: Tls,
-    /// The `ArangoDB` configuration
-    arangodb: Arangodb,
+    /// The `ArangoDB` job store configuration
+    arangodb: Option<Arangodb>,
+    /// The file job store configuration
+    storage: Option<Storage>,
+    /// How long finished jobs are kept in the job store
+    retention: Option<Retention>,
     /// The tracing configuration
     tracing: Option<Tracing>,
     /// A list of hosts.
@@ -230,6 +329,27 @@ pub(crate) struct Arangodb {
     name: String,
 }
 
+/// file job store configuration
+#[derive(Clone, Debug, Default, Deserialize, Eq, Getters, PartialEq, Serialize)]
+#[getset(get = "pub(crate)")]
+pub(crate) struct Storage {
+    /// The directory to keep the job files in
+    path: PathBuf,
+}
+
+/// job retention configuration
+#[derive(Clone, Debug, Default, Deserialize, Eq, Getters, PartialEq, Serialize)]
+#[getset(get = "pub(crate)")]
+pub(crate) struct Retention {
+    /// Remove jobs that started more than this many days ago
+    max_age_days: Option<u32>,
+    /// Keep at most this many jobs for each command on a worker
+    max_jobs: Option<usize>,
+    /// Always keep this many of the most recent failed jobs for each command
+    #[serde(default)]
+    keep_failures: usize,
+}
+
 /// tracing configuration
 #[allow(clippy::struct_excessive_bools)]
 #[derive(Clone, Debug, Default, Deserialize, Eq, Getters, PartialEq, Serialize)]
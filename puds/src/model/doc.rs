@@ -8,12 +8,13 @@
 
 //! job results document
 
-use getset::{Getters, MutGetters, Setters};
+use getset::{Getters, Setters};
+use pudlib::{JobExit, KillReason, OutputLine};
 use serde::Serialize;
 use time::OffsetDateTime;
 use uuid::Uuid;
 
-#[derive(Clone, Debug, Eq, Getters, MutGetters, PartialEq, Serialize, Setters)]
+#[derive(Clone, Debug, Eq, Getters, PartialEq, Serialize, Setters)]
 #[getset(get = "pub(crate)", set = "pub(crate)")]
 pub(crate) struct Job {
     worker_id: Uuid,
@@ -24,11 +25,16 @@ pub(crate) struct Job {
     start_time: OffsetDateTime,
     #[serde(with = "time::serde::iso8601")]
     end_time: OffsetDateTime,
-    #[getset(get_mut = "pub(crate)")]
-    stdout: Vec<String>,
-    #[getset(get_mut = "pub(crate)")]
-    stderr: Vec<String>,
-    status: i32,
+    output: Vec<OutputLine>,
+    stdout_dropped: usize,
+    stderr_dropped: usize,
+    truncated: bool,
+    status: Option<i32>,
+    exit: Option<JobExit>,
+    killed: Option<KillReason>,
+    skipped: bool,
+    requested_by: Option<Uuid>,
+    cancelled_by: Option<Uuid>,
 }
 
 impl Job {
@@ -43,9 +49,16 @@ impl Job {
             end_time: OffsetDateTime::now_utc(),
             id: job_id,
             name: job_name.into(),
-            stdout: vec![],
-            stderr: vec![],
-            status: i32::default(),
+            output: vec![],
+            stdout_dropped: 0,
+            stderr_dropped: 0,
+            truncated: false,
+            status: None,
+            exit: None,
+            killed: None,
+            skipped: false,
+            requested_by: None,
+            cancelled_by: None,
         }
     }
 }
@@ -12,6 +12,7 @@ use crate::{
     endpoints::insecure::insecure_config,
     model::config::{Config, TomlConfig},
     server::Server,
+    store::{self, JobStore},
 };
 use actix::Actor;
 use actix_web::{
@@ -22,7 +23,6 @@ use actix_web::{
 use anyhow::{anyhow, Context, Result};
 use clap::Parser;
 use pudlib::{header, initialize, load, Cli, PudxBinary};
-use ruarango::ConnectionBuilder;
 use rustls::{
     crypto::aws_lc_rs::default_provider,
     pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
@@ -42,7 +42,6 @@ const HEADER_PREFIX: &str = r"██████╗ ██╗   ██╗██
 ██║     ╚██████╔╝██████╔╝███████║
  ╚═╝      ╚═════╝ ╚═════╝ ╚══════╝";
 
-#[allow(clippy::unused_async)]
 pub(crate) async fn run<I, T>(args: Option<I>) -> Result<()>
 where
     I: IntoIterator<Item = T>,
@@ -69,28 +68,26 @@ where
     // Output the pretty header
     header::<Config, dyn Write>(&config, HEADER_PREFIX, Some(&mut io::stdout()))?;
 
-    // Setup and start the server actor
     let socket_addr = *config.socket_addr();
     let workers = usize::from(*config.workers());
-    let server = Server::builder().config(config.clone()).build();
-    let server_data = Data::new(server.start());
 
     // Add config to app data
     let config_c = config.clone();
     let config_data = Data::new(config_c);
 
     if !args.dry_run() {
-        // Setup connection to the database
-        let conn = ConnectionBuilder::default()
-            .url(config.db_url())
-            .username(config.db_user())
-            .password(config.db_pass())
-            .database(config.db_name())
-            .build()
-            .await?;
-
-        // Add connection to app data
-        let conn_data = Data::new(conn);
+        // Open the job store
+        let store = store::open(config.store()).await?;
+
+        // Setup and start the server actor
+        let server = Server::builder()
+            .config(config.clone())
+            .store(store.clone())
+            .build();
+        let server_data = Data::new(server.start());
+
+        // Add the job store to app data
+        let store_data: Data<dyn JobStore> = Data::from(store);
 
         match default_provider().install_default() {
             Ok(()) => info!("aws lc provider initialized"),
@@ -109,7 +106,7 @@ where
             App::new()
                 .app_data(server_data.clone())
                 .app_data(config_data.clone())
-                .app_data(conn_data.clone())
+                .app_data(store_data.clone())
                 .wrap(Compress::default())
                 .service(scope("/v1").configure(insecure_config))
         })
@@ -161,7 +158,9 @@ fn load_tls_config(config: &Config) -> Result<ServerConfig> {
 #[cfg(test)]
 mod test {
     use super::run;
-    use crate::constants::TEST_PATH;
+    use crate::constants::{
+        CONFLICTING_STORES_PATH, INVALID_CALENDAR_PATH, INVALID_COMMAND_PATH, TEST_PATH,
+    };
 
     #[actix_rt::test]
     async fn success() {
@@ -188,6 +187,42 @@ mod test {
         .is_ok());
     }
 
+    #[actix_rt::test]
+    async fn invalid_calendar() {
+        assert!(run(Some(&[
+            env!("CARGO_PKG_NAME"),
+            "--dry-run",
+            "-c",
+            INVALID_CALENDAR_PATH
+        ]))
+        .await
+        .is_err());
+    }
+
+    #[actix_rt::test]
+    async fn invalid_command() {
+        assert!(run(Some(&[
+            env!("CARGO_PKG_NAME"),
+            "--dry-run",
+            "-c",
+            INVALID_COMMAND_PATH
+        ]))
+        .await
+        .is_err());
+    }
+
+    #[actix_rt::test]
+    async fn conflicting_stores() {
+        assert!(run(Some(&[
+            env!("CARGO_PKG_NAME"),
+            "--dry-run",
+            "-c",
+            CONFLICTING_STORES_PATH
+        ]))
+        .await
+        .is_err());
+    }
+
     #[actix_rt::test]
     async fn error() {
         assert!(run::<Vec<&str>, &str>(None).await.is_err());
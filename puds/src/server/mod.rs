@@ -8,25 +8,31 @@
 
 //! Server Actor
 
+mod running;
+mod subscription;
+
+use self::{running::Running, subscription::Subscriptions};
 use crate::{
+    constants::PRUNE_INTERVAL,
     manager::{
         message::{Connect as ManagerConnect, Disconnect as ManagerDisconnect},
         Manager,
     },
     model::config::{Config, TomlConfig},
+    store::{prune, JobStore},
     worker::{
         message::{Connect as WorkerConnect, Disconnect as WorkerDisconnect},
         Worker,
     },
 };
-use actix::{Actor, Context, Handler, MessageResult};
+use actix::{Actor, ActorFutureExt, AsyncContext, Context, Handler, MessageResult, WrapFuture};
 use getset::Getters;
 use pudlib::{
-    reload, ManagerSessionToServer, Schedules, ServerToManagerClient, ServerToWorkerClient,
-    WorkerSessionToServer,
+    reload, Command, ManagerSessionToServer, Schedules, ServerToManagerClient,
+    ServerToWorkerClient, WorkerSessionToServer,
 };
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, BTreeSet, HashMap},
     sync::{
         atomic::{AtomicUsize, Ordering},
         Arc,
@@ -50,6 +56,16 @@ pub(crate) struct Server {
     worker_count: Arc<AtomicUsize>,
     #[builder(default = Arc::new(AtomicUsize::new(0)))]
     manager_count: Arc<AtomicUsize>,
+    #[builder(default = Subscriptions::default())]
+    subscriptions: Subscriptions,
+    #[builder(default = Running::default())]
+    running: Running,
+    #[builder(default, setter(strip_option))]
+    store: Option<Arc<dyn JobStore>>,
+    #[builder(default = false)]
+    pruning: bool,
+    #[builder(default = BTreeSet::new())]
+    seen_workers: BTreeSet<String>,
 }
 
 impl Server {
@@ -109,6 +125,68 @@ impl Server {
         }
     }
 
+    // The default commands, with any overrides for the named worker applied
+    fn commands_for(&self, name: &str) -> BTreeMap<String, Command> {
+        let mut commands = self.config.default().clone();
+        if let Some(overrides) = self.config.overrides().get(name) {
+            for (name, cmd) in overrides {
+                let cmd_c = cmd.clone();
+                *commands.entry(name.clone()).or_insert_with(|| cmd.clone()) = cmd_c;
+            }
+        }
+        commands
+    }
+
+    // The session id of the connected worker with the given name
+    fn worker_id(&self, name: &str) -> Option<Uuid> {
+        self.workers
+            .iter()
+            .find(|(_k, v)| *v.name() == name)
+            .map(|(worker_id, _worker)| *worker_id)
+    }
+
+    // Ask the named worker to run a command now, on behalf of a manager
+    fn run_now(&self, manager_id: Uuid, worker: &str, command: String) {
+        let reason = if let Some(worker_id) = self.worker_id(worker) {
+            if self.commands_for(worker).contains_key(&command) {
+                self.direct_worker_message(
+                    ServerToWorkerClient::Run {
+                        manager_id,
+                        name: command,
+                    },
+                    &worker_id,
+                );
+                return;
+            }
+            format!("command '{command}' is not configured for worker '{worker}'")
+        } else {
+            format!("worker '{worker}' is not connected")
+        };
+        self.direct_manager_message(ServerToManagerClient::RunFailed(reason), &manager_id);
+    }
+
+    // Ask the named worker to cancel one of its jobs, on behalf of a manager
+    fn cancel_job(&self, manager_id: Uuid, worker: String, job_id: Uuid) {
+        if let Some(worker_id) = self.worker_id(&worker) {
+            self.direct_worker_message(
+                ServerToWorkerClient::Cancel {
+                    manager_id,
+                    id: job_id,
+                },
+                &worker_id,
+            );
+        } else {
+            self.direct_manager_message(
+                ServerToManagerClient::CancelResult {
+                    worker,
+                    id: job_id,
+                    cancelled: false,
+                },
+                &manager_id,
+            );
+        }
+    }
+
     pub(crate) fn direct_manager_message(&self, message: ServerToManagerClient, id: &Uuid) {
         if let Some(manager) = self.managers.get(id) {
             manager.addr().do_send(message);
@@ -116,11 +194,55 @@ impl Server {
             error!("cannont send message to manager: {}", id);
         }
     }
+
+    // The workers named in the configuration, and any that have connected
+    // since the server started
+    fn known_workers(&self) -> BTreeSet<String> {
+        self.config
+            .overrides()
+            .keys()
+            .chain(self.config.schedules().keys())
+            .chain(&self.seen_workers)
+            .cloned()
+            .collect()
+    }
+
+    // Remove the jobs the retention policy no longer wants kept.  The policy
+    // is read each time, so a reload picks up any change to it.
+    fn prune(&mut self, ctx: &mut Context<Self>) {
+        let (Some(store), Some(retention)) = (&self.store, self.config.retention()) else {
+            return;
+        };
+        if self.pruning {
+            debug!("the job store is still being pruned");
+            return;
+        }
+        self.pruning = true;
+        let pruning = prune(
+            store.clone(),
+            retention.clone(),
+            self.known_workers(),
+            OffsetDateTime::now_utc(),
+        );
+        let _handle = ctx.spawn(pruning.into_actor(self).map(|res, act, _ctx| {
+            act.pruning = false;
+            match res {
+                Ok(0) => debug!("no jobs pruned"),
+                Ok(count) => info!("pruned {count} job(s) in total"),
+                Err(e) => error!("unable to prune the job store: {e}"),
+            }
+        }));
+    }
 }
 
 // `Server` is an `actix::Actor`
 impl Actor for Server {
     type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.prune(ctx);
+        let _handle = ctx.run_interval(PRUNE_INTERVAL, Self::prune);
+    }
 }
 
 // Handler for worker `Connect` message.
@@ -176,7 +298,9 @@ impl Handler<WorkerDisconnect> for Server {
     fn handle(&mut self, msg: WorkerDisconnect, _ctx: &mut Context<Self>) {
         debug!("handling disconnect message from worker");
         // remove worker
-        if self.workers.remove(&msg.id()).is_some() {
+        if let Some(worker) = self.workers.remove(&msg.id()) {
+            self.running.remove_worker(worker.name());
+
             // broadcast disconnect to all
             self.broadcast(format!("worker disconnected: {}", msg.id()), &None);
 
@@ -194,6 +318,7 @@ impl Handler<ManagerDisconnect> for Server {
     fn handle(&mut self, msg: ManagerDisconnect, _ctx: &mut Context<Self>) {
         debug!("handling disconnect message from manager");
         // remove manager
+        self.subscriptions.unsubscribe(&msg.id());
         if self.managers.remove(&msg.id()).is_some() {
             // broadcast disconnect to all
             self.broadcast(format!("manager disconnected: {}", msg.id()), &None);
@@ -213,13 +338,8 @@ impl Handler<WorkerSessionToServer> for Server {
         debug!("handling message from a worker session");
         match msg {
             WorkerSessionToServer::Initialize { id, name } => {
-                let mut commands = self.config.default().clone();
-                if let Some(overrides) = self.config.overrides().get(&name) {
-                    for (name, cmd) in overrides {
-                        let cmd_c = cmd.clone();
-                        *commands.entry(name.clone()).or_insert_with(|| cmd.clone()) = cmd_c;
-                    }
-                }
+                let _b = self.seen_workers.insert(name.clone());
+                let commands = self.commands_for(&name);
                 let mut schedules = self.config.schedules().clone();
                 let schedule = schedules
                     .remove(&name)
@@ -234,9 +354,54 @@ impl Handler<WorkerSessionToServer> for Server {
                 manager_id,
                 name,
                 schedules,
+                timezone,
             } => {
                 self.direct_manager_message(
-                    ServerToManagerClient::Schedules { name, schedules },
+                    ServerToManagerClient::Schedules {
+                        name,
+                        schedules,
+                        timezone,
+                    },
+                    &manager_id,
+                );
+            }
+            WorkerSessionToServer::JobEvent {
+                requested_by,
+                worker,
+                id,
+                name,
+                event,
+            } => {
+                self.running.record(&worker, id, &name, &event);
+                let mut manager_ids: Vec<Uuid> =
+                    self.subscriptions.subscribers(&worker, &name).collect();
+                if let Some(manager_id) = requested_by {
+                    if !manager_ids.contains(&manager_id) {
+                        manager_ids.push(manager_id);
+                    }
+                }
+                let message = ServerToManagerClient::JobEvent {
+                    worker,
+                    id,
+                    name,
+                    event,
+                };
+                for manager_id in &manager_ids {
+                    self.direct_manager_message(message.clone(), manager_id);
+                }
+            }
+            WorkerSessionToServer::CancelResult {
+                manager_id,
+                worker,
+                id,
+                cancelled,
+            } => {
+                self.direct_manager_message(
+                    ServerToManagerClient::CancelResult {
+                        worker,
+                        id,
+                        cancelled,
+                    },
                     &manager_id,
                 );
             }
@@ -285,6 +450,7 @@ impl Handler<ManagerSessionToServer> for Server {
                         ServerToManagerClient::Schedules {
                             name,
                             schedules: vec![],
+                            timezone: None,
                         },
                         &id,
                     );
@@ -294,9 +460,12 @@ impl Handler<ManagerSessionToServer> for Server {
                 if output.is_empty() {
                     self.direct_manager_message(
                         ServerToManagerClient::QueryReturn {
-                            stdout: vec![],
-                            stderr: vec![],
-                            status: 0,
+                            output: vec![],
+                            truncated: false,
+                            stdout_dropped: 0,
+                            stderr_dropped: 0,
+                            status: None,
+                            exit: None,
                             start_time: OffsetDateTime::now_utc(),
                             end_time: OffsetDateTime::now_utc(),
                             done: true,
@@ -308,9 +477,12 @@ impl Handler<ManagerSessionToServer> for Server {
                     for (idx, job_doc) in output.iter().enumerate() {
                         self.direct_manager_message(
                             ServerToManagerClient::QueryReturn {
-                                stderr: job_doc.stderr().clone(),
-                                stdout: job_doc.stdout().clone(),
+                                output: job_doc.transcript(),
+                                truncated: *job_doc.truncated(),
+                                stdout_dropped: *job_doc.stdout_dropped(),
+                                stderr_dropped: *job_doc.stderr_dropped(),
                                 status: *job_doc.status(),
+                                exit: *job_doc.exit(),
                                 start_time: *job_doc.start_time(),
                                 end_time: *job_doc.end_time(),
                                 done: idx == (output_len - 1),
@@ -320,6 +492,22 @@ impl Handler<ManagerSessionToServer> for Server {
                     }
                 }
             }
+            ManagerSessionToServer::Run {
+                id,
+                worker,
+                command,
+            } => self.run_now(id, &worker, command),
+            ManagerSessionToServer::Tail { id, worker, job } => {
+                info!("manager {id} is following '{worker}'");
+                self.subscriptions.subscribe(id, worker, job);
+            }
+            ManagerSessionToServer::Running(id) => {
+                let running = self.running.list(OffsetDateTime::now_utc());
+                self.direct_manager_message(ServerToManagerClient::Running(running), &id);
+            }
+            ManagerSessionToServer::Cancel { id, worker, job_id } => {
+                self.cancel_job(id, worker, job_id);
+            }
         }
     }
 }
@@ -0,0 +1,142 @@
+// Copyright (c) 2022 pud developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Jobs running right now
+
+use pudlib::{JobEvent, RunningJob, Stream};
+use std::{collections::HashMap, time::Duration};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// The jobs running on every connected worker, tracked from their job events
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Running {
+    jobs: HashMap<Uuid, InFlight>,
+}
+
+// A job that has started but not yet ended
+#[derive(Clone, Debug)]
+struct InFlight {
+    worker: String,
+    name: String,
+    start_time: OffsetDateTime,
+    stdout_lines: usize,
+    stderr_lines: usize,
+}
+
+impl Running {
+    /// Update the running jobs with an event from a worker.  A job already
+    /// being tracked keeps its counts when its start is replayed.
+    pub(crate) fn record(&mut self, worker: &str, id: Uuid, name: &str, event: &JobEvent) {
+        match event {
+            JobEvent::Started(start_time) => {
+                _ = self.jobs.entry(id).or_insert_with(|| InFlight {
+                    worker: worker.to_string(),
+                    name: name.to_string(),
+                    start_time: *start_time,
+                    stdout_lines: 0,
+                    stderr_lines: 0,
+                });
+            }
+            JobEvent::Output(line) => {
+                if let Some(job) = self.jobs.get_mut(&id) {
+                    match line.stream() {
+                        Stream::Stdout => job.stdout_lines += 1,
+                        Stream::Stderr => job.stderr_lines += 1,
+                    }
+                }
+            }
+            JobEvent::Exit(_) => {}
+            JobEvent::Ended => {
+                let _old = self.jobs.remove(&id);
+            }
+        }
+    }
+
+    /// Forget the jobs of a worker that has disconnected
+    pub(crate) fn remove_worker(&mut self, worker: &str) {
+        self.jobs.retain(|_id, job| job.worker != worker);
+    }
+
+    /// The running jobs as of now, ordered by worker and start time
+    pub(crate) fn list(&self, now: OffsetDateTime) -> Vec<RunningJob> {
+        let mut running: Vec<RunningJob> = self
+            .jobs
+            .iter()
+            .map(|(id, job)| {
+                RunningJob::builder()
+                    .worker(job.worker.clone())
+                    .id(*id)
+                    .name(job.name.clone())
+                    .start_time(job.start_time)
+                    .elapsed(Duration::try_from(now - job.start_time).unwrap_or_default())
+                    .stdout_lines(job.stdout_lines)
+                    .stderr_lines(job.stderr_lines)
+                    .build()
+            })
+            .collect();
+        running.sort_by(|x, y| {
+            x.worker()
+                .cmp(y.worker())
+                .then(x.start_time().cmp(y.start_time()))
+        });
+        running
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Running;
+    use pudlib::{JobEvent, OutputLine, Stream};
+    use std::time::Duration;
+    use time::OffsetDateTime;
+    use uuid::Uuid;
+
+    #[test]
+    fn tracks_job_until_ended() {
+        let mut running = Running::default();
+        let id = Uuid::new_v4();
+        let start_time = OffsetDateTime::now_utc();
+        running.record("yoda", id, "rustup", &JobEvent::Started(start_time));
+        let line = |stream, line| JobEvent::Output(OutputLine::new(Duration::ZERO, stream, line));
+        running.record("yoda", id, "rustup", &line(Stream::Stdout, "a"));
+        running.record("yoda", id, "rustup", &line(Stream::Stdout, "b"));
+        running.record("yoda", id, "rustup", &line(Stream::Stderr, "c"));
+
+        let jobs = running.list(OffsetDateTime::now_utc());
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].worker(), "yoda");
+        assert_eq!(jobs[0].name(), "rustup");
+        assert_eq!(*jobs[0].stdout_lines(), 2);
+        assert_eq!(*jobs[0].stderr_lines(), 1);
+        assert_eq!(*jobs[0].start_time(), start_time);
+
+        // a replayed start doesn't reset the counts
+        running.record("yoda", id, "rustup", &JobEvent::Started(start_time));
+        assert_eq!(
+            *running.list(OffsetDateTime::now_utc())[0].stdout_lines(),
+            2
+        );
+
+        running.record("yoda", id, "rustup", &JobEvent::Ended);
+        assert!(running.list(OffsetDateTime::now_utc()).is_empty());
+    }
+
+    #[test]
+    fn forgets_disconnected_worker() {
+        let mut running = Running::default();
+        let started = JobEvent::Started(OffsetDateTime::now_utc());
+        running.record("yoda", Uuid::new_v4(), "rustup", &started);
+        running.record("luke", Uuid::new_v4(), "rustup", &started);
+        running.remove_worker("yoda");
+
+        let jobs = running.list(OffsetDateTime::now_utc());
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].worker(), "luke");
+    }
+}
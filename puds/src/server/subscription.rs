@@ -0,0 +1,95 @@
+// Copyright (c) 2022 pud developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Live job output subscriptions
+
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// The managers interested in the live output of jobs, keyed by manager id
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Subscriptions {
+    subscriptions: HashMap<Uuid, Subscription>,
+}
+
+// What a manager wants to follow
+#[derive(Clone, Debug)]
+struct Subscription {
+    // the name of the worker the jobs run on
+    worker: String,
+    // the job name to follow, or every job when not given
+    job: Option<String>,
+}
+
+impl Subscriptions {
+    /// Follow the jobs on the given worker, replacing any previous
+    /// subscription for the manager
+    pub(crate) fn subscribe(&mut self, manager_id: Uuid, worker: String, job: Option<String>) {
+        let _old = self
+            .subscriptions
+            .insert(manager_id, Subscription { worker, job });
+    }
+
+    /// Stop following jobs for the given manager
+    pub(crate) fn unsubscribe(&mut self, manager_id: &Uuid) {
+        let _old = self.subscriptions.remove(manager_id);
+    }
+
+    /// The managers following the given job on the given worker
+    pub(crate) fn subscribers<'a>(
+        &'a self,
+        worker: &'a str,
+        job: &'a str,
+    ) -> impl Iterator<Item = Uuid> + 'a {
+        self.subscriptions
+            .iter()
+            .filter(move |(_id, sub)| {
+                sub.worker == worker && sub.job.as_ref().is_none_or(|name| name == job)
+            })
+            .map(|(id, _sub)| *id)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Subscriptions;
+    use uuid::Uuid;
+
+    #[test]
+    fn matches_worker_and_job() {
+        let mut subscriptions = Subscriptions::default();
+        let all = Uuid::new_v4();
+        let one = Uuid::new_v4();
+        subscriptions.subscribe(all, "yoda".to_string(), None);
+        subscriptions.subscribe(one, "yoda".to_string(), Some("rustup".to_string()));
+
+        let mut subscribers = subscriptions
+            .subscribers("yoda", "rustup")
+            .collect::<Vec<_>>();
+        subscribers.sort();
+        let mut expected = vec![all, one];
+        expected.sort();
+        assert_eq!(subscribers, expected);
+        assert_eq!(
+            subscriptions
+                .subscribers("yoda", "backup")
+                .collect::<Vec<_>>(),
+            [all]
+        );
+        assert_eq!(subscriptions.subscribers("luke", "rustup").count(), 0);
+    }
+
+    #[test]
+    fn unsubscribe_stops_matching() {
+        let mut subscriptions = Subscriptions::default();
+        let id = Uuid::new_v4();
+        subscriptions.subscribe(id, "yoda".to_string(), None);
+        subscriptions.unsubscribe(&id);
+        assert_eq!(subscriptions.subscribers("yoda", "rustup").count(), 0);
+    }
+}
@@ -0,0 +1,236 @@
+// Copyright (c) 2022 pud developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! `ArangoDB` job store
+
+use super::{JobStore, StoreFuture};
+use crate::model::doc::Job;
+use anyhow::{anyhow, Result};
+use pudlib::{JobDoc, JobQuery, Sort, StatusFilter};
+use ruarango::{
+    coll, cursor::input::CreateConfigBuilder, doc, doc::input::OverwriteMode, Collection,
+    Connection, ConnectionBuilder, Cursor, DocMetaResult, Document,
+};
+use std::{collections::HashMap, fmt};
+use time::{
+    format_description::well_known::{
+        iso8601::{Config, EncodedConfig},
+        Iso8601,
+    },
+    OffsetDateTime,
+};
+use tracing::{debug, info};
+use uuid::Uuid;
+
+/// Keeps a collection of job documents per worker in `ArangoDB`
+pub(crate) struct ArangodbStore {
+    conn: Connection,
+}
+
+impl ArangodbStore {
+    pub(crate) async fn connect(url: &str, user: &str, password: &str, name: &str) -> Result<Self> {
+        let conn = ConnectionBuilder::default()
+            .url(url)
+            .username(user)
+            .password(password)
+            .database(name)
+            .build()
+            .await?;
+        Ok(Self { conn })
+    }
+}
+
+impl fmt::Debug for ArangodbStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ArangodbStore").finish_non_exhaustive()
+    }
+}
+
+impl JobStore for ArangodbStore {
+    fn create_collection(&self, name: &str) -> StoreFuture<()> {
+        let conn = self.conn.clone();
+        let name = name.to_string();
+        Box::pin(async move {
+            if let Err(e) = Collection::collection(&conn, &name).await {
+                debug!("collection not found: {e}");
+                let coll_config = coll::input::ConfigBuilder::default()
+                    .name(&name)
+                    .build()
+                    .map_err(|e| anyhow!("{e}"))?;
+                _ = Collection::create(&conn, &coll_config).await?;
+                info!("collection '{name}' created successfully!");
+            }
+            Ok(())
+        })
+    }
+
+    fn insert_job(&self, collection: &str, job: Job) -> StoreFuture<()> {
+        let conn = self.conn.clone();
+        // The job id is used as the document key, so a job replayed by a
+        // worker that never saw it acknowledged isn't stored twice
+        let id = *job.id();
+        let config = serde_json::to_value(job)
+            .map_err(anyhow::Error::from)
+            .and_then(|mut doc| {
+                if let Some(fields) = doc.as_object_mut() {
+                    _ = fields.insert("_key".to_string(), id.to_string().into());
+                }
+                doc::input::CreateConfigBuilder::default()
+                    .collection(collection)
+                    .document(doc)
+                    .overwrite_mode(OverwriteMode::Ignore)
+                    .build()
+                    .map_err(|e| anyhow!("{e}"))
+            });
+        Box::pin(async move {
+            debug!("creating job document");
+            let doc_meta_res: DocMetaResult<(), ()> = Document::create(&conn, config?).await;
+            if let Some(doc_meta) = doc_meta_res?.right() {
+                info!("job document stored: {}", doc_meta.id());
+            }
+            Ok(())
+        })
+    }
+
+    fn query_jobs(&self, query: &JobQuery) -> StoreFuture<Vec<JobDoc>> {
+        let conn = self.conn.clone();
+        let config = to_aql(query).and_then(|(aql, bind_vars)| {
+            CreateConfigBuilder::default()
+                .query(aql)
+                .bind_vars(bind_vars)
+                .count(true)
+                .build()
+                .map_err(|e| anyhow!("{e}"))
+        });
+        Box::pin(async move {
+            let meta = Cursor::create::<JobDoc>(&conn, config?)
+                .await?
+                .right_safe()
+                .map_err(|_e| anyhow!("no cursor meta"))?;
+            meta.result()
+                .clone()
+                .ok_or_else(|| anyhow!("no cursor meta result"))
+        })
+    }
+
+    fn collections(&self) -> StoreFuture<Vec<String>> {
+        let conn = self.conn.clone();
+        Box::pin(async move {
+            let collections = Collection::collections(&conn, true)
+                .await?
+                .right_safe()
+                .map_err(|_e| anyhow!("no collections response"))?;
+            Ok(collections
+                .result()
+                .iter()
+                .map(|coll| coll.name().clone())
+                .collect())
+        })
+    }
+
+    fn remove_jobs(&self, collection: &str, ids: Vec<Uuid>) -> StoreFuture<usize> {
+        let conn = self.conn.clone();
+        // the ids are formatted here rather than bound, they are only ever uuids
+        let ids = ids
+            .iter()
+            .map(|id| format!("\"{id}\""))
+            .collect::<Vec<String>>()
+            .join(", ");
+        let config = CreateConfigBuilder::default()
+            .query(format!(
+                "FOR job IN @@collection FILTER job.id IN [{ids}] \
+                 REMOVE job IN @@collection RETURN OLD.id"
+            ))
+            .bind_vars(HashMap::from([(
+                "@collection".to_string(),
+                collection.to_string(),
+            )]))
+            .count(true)
+            .build()
+            .map_err(|e| anyhow!("{e}"));
+        Box::pin(async move {
+            let meta = Cursor::create::<Uuid>(&conn, config?)
+                .await?
+                .right_safe()
+                .map_err(|_e| anyhow!("no cursor meta"))?;
+            Ok(meta.result().as_ref().map_or(0, Vec::len))
+        })
+    }
+}
+
+// Translate a job query into AQL.  Every value from the query is passed as a
+// bind parameter, apart from the limit which is a number.
+fn to_aql(query: &JobQuery) -> Result<(String, HashMap<String, String>)> {
+    let mut aql = vec!["FOR job IN @@collection".to_string()];
+    let mut bind_vars = HashMap::new();
+    _ = bind_vars.insert("@collection".to_string(), query.worker().clone());
+
+    if let Some(name) = query.name() {
+        aql.push("FILTER job.name == @name".to_string());
+        _ = bind_vars.insert("name".to_string(), name.clone());
+    }
+    if let Some(since) = query.since() {
+        aql.push("FILTER job.start_time >= @since".to_string());
+        _ = bind_vars.insert("since".to_string(), format_time(since)?);
+    }
+    if let Some(until) = query.until() {
+        aql.push("FILTER job.start_time < @until".to_string());
+        _ = bind_vars.insert("until".to_string(), format_time(until)?);
+    }
+    match query.status() {
+        StatusFilter::All => {}
+        StatusFilter::Succeeded => aql.push("FILTER job.status == 0 && !job.skipped".to_string()),
+        StatusFilter::Failed => aql.push("FILTER job.status != 0 && !job.skipped".to_string()),
+    }
+    aql.push(match query.sort() {
+        Sort::NewestFirst => "SORT job.start_time DESC".to_string(),
+        Sort::OldestFirst => "SORT job.start_time ASC".to_string(),
+    });
+    if let Some(limit) = query.limit() {
+        aql.push(format!("LIMIT {limit}"));
+    }
+    aql.push("RETURN job".to_string());
+    Ok((aql.join(" "), bind_vars))
+}
+
+// The format `time::serde::iso8601` writes job document times in
+const DOC_TIME_FORMAT: EncodedConfig = Config::DEFAULT.set_year_is_six_digits(true).encode();
+
+// Format a time the way the job documents store them, so they compare as strings
+fn format_time(time: OffsetDateTime) -> Result<String> {
+    Ok(time.format(&Iso8601::<DOC_TIME_FORMAT>)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::to_aql;
+    use anyhow::Result;
+    use pudlib::{JobQuery, StatusFilter};
+
+    #[test]
+    fn query_to_aql() -> Result<()> {
+        let query = JobQuery::builder()
+            .worker("yoda")
+            .name("rustup")
+            .status(StatusFilter::Failed)
+            .limit(5)
+            .build();
+        let (aql, bind_vars) = to_aql(&query)?;
+        assert_eq!(
+            aql,
+            "FOR job IN @@collection FILTER job.name == @name FILTER job.status != 0 \
+             && !job.skipped SORT job.start_time DESC LIMIT 5 RETURN job"
+        );
+        assert_eq!(
+            bind_vars.get("@collection").map(String::as_str),
+            Some("yoda")
+        );
+        assert_eq!(bind_vars.get("name").map(String::as_str), Some("rustup"));
+        Ok(())
+    }
+}
@@ -0,0 +1,283 @@
+// Copyright (c) 2022 pud developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! File backed job store
+
+use super::{JobStore, StoreFuture};
+use crate::model::doc::Job;
+use actix_web::web::block;
+use anyhow::{anyhow, Result};
+use pudlib::{JobDoc, JobQuery};
+use serde::Deserialize;
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use tracing::info;
+use uuid::Uuid;
+
+const EXTENSION: &str = "jsonl";
+
+/// Keeps the job documents for each worker as JSON lines in a file named for
+/// the worker, so no outside service is needed
+#[derive(Clone, Debug)]
+pub(crate) struct FileStore {
+    path: PathBuf,
+    // The ids of the jobs in each collection file, read the first time a job
+    // is inserted into it.  Held while a collection file is written, so a job
+    // appended while the file is being pruned isn't lost.
+    stored: Arc<Mutex<StoredIds>>,
+}
+
+type StoredIds = HashMap<PathBuf, HashSet<Uuid>>;
+
+// Just the id of a stored job, so the rest of the document can be skipped
+#[derive(Deserialize)]
+struct StoredId {
+    #[serde(default)]
+    id: Uuid,
+}
+
+impl FileStore {
+    pub(crate) fn open(path: &Path) -> Result<Self> {
+        fs::create_dir_all(path)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            stored: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    // Worker names come from the connecting worker, so don't let one escape
+    // the store directory
+    fn collection_path(&self, name: &str) -> Result<PathBuf> {
+        if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+            return Err(anyhow!("'{name}' cannot be used as a collection name"));
+        }
+        Ok(self.path.join(format!("{name}.{EXTENSION}")))
+    }
+}
+
+impl JobStore for FileStore {
+    fn create_collection(&self, name: &str) -> StoreFuture<()> {
+        let path = self.collection_path(name);
+        let name = name.to_string();
+        Box::pin(async move {
+            let path = path?;
+            block(move || -> Result<()> {
+                if !path.exists() {
+                    _ = OpenOptions::new().create(true).append(true).open(&path)?;
+                    info!("collection '{name}' created successfully!");
+                }
+                Ok(())
+            })
+            .await?
+        })
+    }
+
+    fn insert_job(&self, collection: &str, job: Job) -> StoreFuture<()> {
+        let path = self.collection_path(collection);
+        let stored = self.stored.clone();
+        Box::pin(async move {
+            let path = path?;
+            block(move || -> Result<()> {
+                let mut line = serde_json::to_vec(&job)?;
+                line.push(b'\n');
+                let mut stored = stored.lock().map_err(|e| anyhow!("{e}"))?;
+                let ids = match stored.entry(path.clone()) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => entry.insert(read_ids(&path)?),
+                };
+                // a worker replays any job it never saw acknowledged, which
+                // may already be stored
+                if ids.contains(job.id()) {
+                    info!("job document already stored: {}", job.id());
+                    return Ok(());
+                }
+                let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+                file.write_all(&line)?;
+                _ = ids.insert(*job.id());
+                info!("job document created: {}", job.id());
+                Ok(())
+            })
+            .await?
+        })
+    }
+
+    fn query_jobs(&self, query: &JobQuery) -> StoreFuture<Vec<JobDoc>> {
+        let path = self.collection_path(query.worker());
+        let query = query.clone();
+        Box::pin(async move {
+            let path = path?;
+            block(move || -> Result<Vec<JobDoc>> {
+                if !path.exists() {
+                    return Ok(vec![]);
+                }
+                let jobs = BufReader::new(File::open(&path)?)
+                    .lines()
+                    .map(|line| Ok(serde_json::from_str::<JobDoc>(&line?)?))
+                    .collect::<Result<Vec<JobDoc>>>()?;
+                Ok(query.apply(jobs))
+            })
+            .await?
+        })
+    }
+
+    fn collections(&self) -> StoreFuture<Vec<String>> {
+        let path = self.path.clone();
+        Box::pin(async move {
+            block(move || -> Result<Vec<String>> {
+                let mut names = vec![];
+                for entry in fs::read_dir(&path)? {
+                    let path = entry?.path();
+                    if path.extension().is_some_and(|ext| ext == EXTENSION) {
+                        if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                            names.push(name.to_string());
+                        }
+                    }
+                }
+                names.sort();
+                Ok(names)
+            })
+            .await?
+        })
+    }
+
+    fn remove_jobs(&self, collection: &str, ids: Vec<Uuid>) -> StoreFuture<usize> {
+        let path = self.collection_path(collection);
+        let stored = self.stored.clone();
+        Box::pin(async move {
+            let path = path?;
+            block(move || -> Result<usize> {
+                let mut stored = stored.lock().map_err(|e| anyhow!("{e}"))?;
+                if ids.is_empty() || !path.exists() {
+                    return Ok(0);
+                }
+                let mut kept = vec![];
+                let mut removed = 0;
+                for line in BufReader::new(File::open(&path)?).lines() {
+                    let line = line?;
+                    if ids.contains(serde_json::from_str::<JobDoc>(&line)?.id()) {
+                        removed += 1;
+                    } else {
+                        kept.push(line);
+                    }
+                }
+                if removed > 0 {
+                    // Write the kept jobs beside the collection, then swap it in
+                    let tmp_path = path.with_extension(format!("{EXTENSION}.tmp"));
+                    let mut file = File::create(&tmp_path)?;
+                    for line in &kept {
+                        writeln!(file, "{line}")?;
+                    }
+                    file.sync_all()?;
+                    fs::rename(&tmp_path, &path)?;
+                    if let Some(stored_ids) = stored.get_mut(&path) {
+                        stored_ids.retain(|id| !ids.contains(id));
+                    }
+                }
+                Ok(removed)
+            })
+            .await?
+        })
+    }
+}
+
+// The ids of the jobs in a collection file
+fn read_ids(path: &Path) -> Result<HashSet<Uuid>> {
+    if !path.exists() {
+        return Ok(HashSet::new());
+    }
+    BufReader::new(File::open(path)?)
+        .lines()
+        .map(|line| Ok(serde_json::from_str::<StoredId>(&line?)?.id))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::FileStore;
+    use crate::{model::doc::Job, store::JobStore};
+    use anyhow::Result;
+    use pudlib::JobQuery;
+    use std::fs;
+    use tempfile::tempdir;
+    use uuid::Uuid;
+
+    #[actix_rt::test]
+    async fn insert_job_appends_a_line() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path();
+        let store = FileStore::open(path)?;
+        store.create_collection("yoda").await?;
+        let job = Job::new(Uuid::new_v4(), "yoda", Uuid::new_v4(), "rustup");
+        store.insert_job("yoda", job.clone()).await?;
+        // a replayed job is only stored once
+        store.insert_job("yoda", job.clone()).await?;
+        let other = Job::new(Uuid::new_v4(), "yoda", Uuid::new_v4(), "rustup");
+        store.insert_job("yoda", other).await?;
+        // including after a restart of the server
+        FileStore::open(path)?.insert_job("yoda", job).await?;
+
+        let contents = fs::read_to_string(path.join("yoda.jsonl"))?;
+        assert_eq!(contents.lines().count(), 2);
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    async fn query_jobs_filters() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path();
+        let store = FileStore::open(path)?;
+        for name in ["rustup", "backup", "rustup"] {
+            let job = Job::new(Uuid::new_v4(), "yoda", Uuid::new_v4(), name);
+            store.insert_job("yoda", job).await?;
+        }
+
+        let query = JobQuery::builder().worker("yoda").name("rustup").build();
+        let docs = store.query_jobs(&query).await?;
+        assert_eq!(docs.len(), 2);
+        assert!(docs.iter().all(|doc| doc.name() == "rustup"));
+
+        let query = JobQuery::builder().worker("luke").build();
+        assert!(store.query_jobs(&query).await?.is_empty());
+
+        let query = JobQuery::builder().worker("../yoda").build();
+        assert!(store.query_jobs(&query).await.is_err());
+        let job = Job::new(Uuid::new_v4(), "yoda", Uuid::new_v4(), "rustup");
+        assert!(store.insert_job("/tmp/yoda", job).await.is_err());
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    async fn remove_jobs_keeps_the_rest() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path();
+        let store = FileStore::open(path)?;
+        let mut ids = vec![];
+        for name in ["rustup", "backup", "rustup"] {
+            let id = Uuid::new_v4();
+            ids.push(id);
+            store
+                .insert_job("yoda", Job::new(Uuid::new_v4(), "yoda", id, name))
+                .await?;
+        }
+        store.create_collection("luke").await?;
+        assert_eq!(store.collections().await?, vec!["luke", "yoda"]);
+
+        assert_eq!(store.remove_jobs("yoda", vec![ids[0], ids[2]]).await?, 2);
+        let query = JobQuery::builder().worker("yoda").build();
+        let docs = store.query_jobs(&query).await?;
+        assert_eq!(docs.len(), 1);
+        assert_eq!(*docs[0].id(), ids[1]);
+        assert_eq!(store.remove_jobs("yoda", vec![ids[0]]).await?, 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,63 @@
+// Copyright (c) 2022 pud developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Job document storage
+
+#[cfg(feature = "arangodb")]
+mod arangodb;
+mod file;
+mod prune;
+
+#[cfg(feature = "arangodb")]
+use self::arangodb::ArangodbStore;
+use self::file::FileStore;
+pub(crate) use self::prune::prune;
+use crate::model::{config::Store, doc::Job};
+use anyhow::Result;
+use pudlib::{JobDoc, JobQuery};
+use std::{fmt::Debug, future::Future, pin::Pin, sync::Arc};
+use uuid::Uuid;
+
+/// The future returned by the `JobStore` operations
+pub(crate) type StoreFuture<T> = Pin<Box<dyn Future<Output = Result<T>>>>;
+
+/// Somewhere to keep the documents of finished jobs
+pub(crate) trait JobStore: Debug + Send + Sync {
+    /// Make sure the collection for the named worker exists
+    fn create_collection(&self, name: &str) -> StoreFuture<()>;
+
+    /// Add a finished job to the collection for the named worker
+    fn insert_job(&self, collection: &str, job: Job) -> StoreFuture<()>;
+
+    /// Find the stored jobs matching a query
+    fn query_jobs(&self, query: &JobQuery) -> StoreFuture<Vec<JobDoc>>;
+
+    /// The names of the workers with a collection
+    fn collections(&self) -> StoreFuture<Vec<String>>;
+
+    /// Remove the given jobs from the collection for the named worker,
+    /// returning how many were removed
+    fn remove_jobs(&self, collection: &str, ids: Vec<Uuid>) -> StoreFuture<usize>;
+}
+
+/// Open the configured job store
+#[cfg_attr(not(feature = "arangodb"), allow(clippy::unused_async))]
+pub(crate) async fn open(store: &Store) -> Result<Arc<dyn JobStore>> {
+    match store {
+        Store::File { path } => Ok(Arc::new(FileStore::open(path)?)),
+        #[cfg(feature = "arangodb")]
+        Store::Arangodb {
+            url,
+            user,
+            password,
+            name,
+        } => Ok(Arc::new(
+            ArangodbStore::connect(url, user, password, name).await?,
+        )),
+    }
+}
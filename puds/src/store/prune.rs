@@ -0,0 +1,239 @@
+// Copyright (c) 2022 pud developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Job retention
+
+use super::JobStore;
+use crate::model::config::Retention;
+use anyhow::Result;
+use pudlib::{JobDoc, JobQuery};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
+use time::{Duration, OffsetDateTime};
+use tracing::{debug, error, info};
+use uuid::Uuid;
+
+/// Remove the jobs that fall outside of the retention policy from the
+/// collections of the given workers, logging what was removed.  Any other
+/// collection in the store is left alone.  Returns the total removed.
+pub(crate) async fn prune(
+    store: Arc<dyn JobStore>,
+    retention: Retention,
+    workers: BTreeSet<String>,
+    now: OffsetDateTime,
+) -> Result<usize> {
+    let mut total = 0;
+    for worker in store.collections().await? {
+        if !workers.contains(&worker) {
+            debug!("not pruning '{worker}', it is not a known worker");
+            continue;
+        }
+        let query = JobQuery::builder().worker(worker.clone()).build();
+        let jobs = match store.query_jobs(&query).await {
+            Ok(jobs) => jobs,
+            Err(e) => {
+                error!("unable to read the jobs of '{worker}': {e}");
+                continue;
+            }
+        };
+        let expired = expired(&jobs, &retention, now);
+        if expired.is_empty() {
+            continue;
+        }
+
+        let mut by_name = BTreeMap::<&str, usize>::new();
+        for job in &expired {
+            *by_name.entry(job.name()).or_default() += 1;
+        }
+        let ids: Vec<Uuid> = expired.iter().map(|job| *job.id()).collect();
+        let removed = match store.remove_jobs(&worker, ids).await {
+            Ok(removed) => removed,
+            Err(e) => {
+                error!("unable to prune the jobs of '{worker}': {e}");
+                continue;
+            }
+        };
+        info!("pruned {removed} job(s) from '{worker}'");
+        for (name, count) in by_name {
+            info!("     {name}: {count}");
+        }
+        total += removed;
+    }
+    Ok(total)
+}
+
+// The jobs of a single worker that the retention policy says should go
+fn expired<'a>(jobs: &'a [JobDoc], retention: &Retention, now: OffsetDateTime) -> Vec<&'a JobDoc> {
+    // an age reaching back before the earliest time is no limit at all
+    let oldest = retention
+        .max_age_days()
+        .and_then(|days| now.checked_sub(Duration::days(i64::from(days))));
+    let mut by_name = BTreeMap::<&str, Vec<&JobDoc>>::new();
+    for job in jobs {
+        by_name.entry(job.name()).or_default().push(job);
+    }
+
+    let mut expired = vec![];
+    for mut jobs in by_name.into_values() {
+        jobs.sort_by(|x, y| y.start_time().cmp(x.start_time()));
+        let mut failures = 0;
+        for (idx, job) in jobs.into_iter().enumerate() {
+            if *job.status() != Some(0) && !*job.skipped() {
+                failures += 1;
+                if failures <= *retention.keep_failures() {
+                    continue;
+                }
+            }
+            let too_old = oldest.is_some_and(|oldest| *job.start_time() < oldest);
+            let too_many = retention.max_jobs().is_some_and(|max| idx >= max);
+            if too_old || too_many {
+                expired.push(job);
+            }
+        }
+    }
+    expired
+}
+
+#[cfg(test)]
+mod test {
+    use super::{expired, prune};
+    use crate::{
+        model::{config::Retention, doc::Job},
+        store::{FileStore, JobStore},
+    };
+    use anyhow::Result;
+    use pudlib::{JobDoc, JobQuery};
+    use std::{collections::BTreeSet, fs, sync::Arc};
+    use tempfile::tempdir;
+    use time::{macros::datetime, OffsetDateTime};
+    use uuid::Uuid;
+
+    const NOW: OffsetDateTime = datetime!(2026-10-16 12:00 UTC);
+
+    // A job document, as the worker session stores it
+    fn job(name: &str, start_time: OffsetDateTime, status: Option<i32>) -> Result<JobDoc> {
+        let mut job = Job::new(Uuid::new_v4(), "yoda", Uuid::new_v4(), name);
+        _ = job
+            .set_start_time(start_time)
+            .set_end_time(start_time)
+            .set_status(status);
+        Ok(serde_json::from_value(serde_json::to_value(job)?)?)
+    }
+
+    fn retention(json: &str) -> Result<Retention> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    #[test]
+    fn nothing_expires_without_limits() -> Result<()> {
+        let jobs = vec![job("rustup", datetime!(2020-01-01 00:00 UTC), Some(0))?];
+        assert!(expired(&jobs, &retention("{}")?, NOW).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn huge_max_age_keeps_everything() -> Result<()> {
+        let jobs = vec![job("rustup", datetime!(2020-01-01 00:00 UTC), Some(0))?];
+        let found = expired(&jobs, &retention(r#"{"max_age_days":4000000}"#)?, NOW);
+        assert!(found.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn max_age_and_max_jobs_per_command() -> Result<()> {
+        let jobs = vec![
+            job("rustup", datetime!(2026-10-16 10:00 UTC), Some(0))?,
+            job("rustup", datetime!(2026-10-15 10:00 UTC), Some(0))?,
+            job("rustup", datetime!(2026-10-14 10:00 UTC), Some(0))?,
+            job("backup", datetime!(2026-10-14 10:00 UTC), Some(0))?,
+            job("backup", datetime!(2026-09-01 10:00 UTC), Some(0))?,
+        ];
+        let found = expired(
+            &jobs,
+            &retention(r#"{"max_age_days":30,"max_jobs":2}"#)?,
+            NOW,
+        );
+        assert_eq!(found.len(), 2);
+        assert!(found
+            .iter()
+            .any(|job| job.name() == "rustup" && job.start_time().day() == 14));
+        assert!(found
+            .iter()
+            .any(|job| job.name() == "backup" && job.start_time().day() == 1));
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_recent_failures() -> Result<()> {
+        let jobs = vec![
+            job("rustup", datetime!(2026-10-16 10:00 UTC), Some(0))?,
+            job("rustup", datetime!(2026-10-15 10:00 UTC), Some(1))?,
+            job("rustup", datetime!(2026-10-14 10:00 UTC), Some(1))?,
+            job("rustup", datetime!(2026-10-13 10:00 UTC), Some(0))?,
+        ];
+        let found = expired(
+            &jobs,
+            &retention(r#"{"max_jobs":1,"keep_failures":1}"#)?,
+            NOW,
+        );
+        assert_eq!(found.len(), 2);
+        assert!(found.iter().all(|job| job.start_time().day() <= 14));
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_jobs_that_never_exited() -> Result<()> {
+        let jobs = vec![
+            job("rustup", datetime!(2026-10-16 10:00 UTC), Some(0))?,
+            // lost, so it never reported an exit
+            job("rustup", datetime!(2026-10-15 10:00 UTC), None)?,
+            job("rustup", datetime!(2026-10-14 10:00 UTC), Some(0))?,
+        ];
+        let found = expired(
+            &jobs,
+            &retention(r#"{"max_jobs":1,"keep_failures":1}"#)?,
+            NOW,
+        );
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].start_time().day(), 14);
+        Ok(())
+    }
+
+    #[actix_rt::test]
+    async fn prunes_only_known_workers() -> Result<()> {
+        let dir = tempdir()?;
+        let store = FileStore::open(dir.path())?;
+        for worker in ["luke", "yoda"] {
+            store.create_collection(worker).await?;
+            for _ in 0..2 {
+                let job = Job::new(Uuid::new_v4(), worker, Uuid::new_v4(), "rustup");
+                store.insert_job(worker, job).await?;
+            }
+        }
+        fs::write(dir.path().join("broken.jsonl"), "not a job\n")?;
+
+        let workers: BTreeSet<String> = ["broken", "yoda"].map(String::from).into();
+        let store: Arc<dyn JobStore> = Arc::new(store);
+        let removed = prune(
+            store.clone(),
+            retention(r#"{"max_jobs":1}"#)?,
+            workers,
+            OffsetDateTime::now_utc(),
+        )
+        .await?;
+        assert_eq!(removed, 1);
+
+        let luke = JobQuery::builder().worker("luke").build();
+        assert_eq!(store.query_jobs(&luke).await?.len(), 2);
+        let yoda = JobQuery::builder().worker("yoda").build();
+        assert_eq!(store.query_jobs(&yoda).await?.len(), 1);
+        Ok(())
+    }
+}
@@ -14,6 +14,7 @@ use getset::Getters;
 use pudlib::ServerToWorkerClient;
 
 pub(crate) mod message;
+pub(crate) mod output;
 pub(crate) mod session;
 
 // Worker information stored with server on connect
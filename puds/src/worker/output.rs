@@ -0,0 +1,238 @@
+// Copyright (c) 2022 pud developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Captured job output
+
+use crate::model::doc::Job;
+use pudlib::{OutputLimits, OutputLine, Stream};
+use std::collections::VecDeque;
+
+/// The stdout and stderr captured for an in-flight job
+#[derive(Clone, Debug)]
+pub(crate) struct JobOutput {
+    stdout: Capture,
+    stderr: Capture,
+}
+
+impl JobOutput {
+    pub(crate) fn new(limits: OutputLimits) -> Self {
+        Self {
+            stdout: Capture::new(limits),
+            stderr: Capture::new(limits),
+        }
+    }
+
+    pub(crate) fn push(&mut self, line: OutputLine) {
+        match line.stream() {
+            Stream::Stdout => self.stdout.push(line),
+            Stream::Stderr => self.stderr.push(line),
+        }
+    }
+
+    /// Move the captured output into the job document, with the lines of
+    /// both streams merged in the order they were written
+    pub(crate) fn finish(self, job: &mut Job) {
+        let (stdout, stdout_dropped) = self.stdout.finish();
+        let (stderr, stderr_dropped) = self.stderr.finish();
+        _ = job
+            .set_output(merge(stdout, stderr))
+            .set_stdout_dropped(stdout_dropped)
+            .set_stderr_dropped(stderr_dropped)
+            .set_truncated(stdout_dropped > 0 || stderr_dropped > 0);
+    }
+}
+
+// One output stream.  Lines fill the head until it is full, every line after
+// that goes to the tail, which drops its oldest lines to stay within limits.
+#[derive(Clone, Debug)]
+struct Capture {
+    head_lines: usize,
+    head_bytes: usize,
+    tail_lines: usize,
+    tail_bytes: usize,
+    head: Vec<OutputLine>,
+    head_used: usize,
+    head_full: bool,
+    tail: VecDeque<OutputLine>,
+    tail_used: usize,
+    dropped: usize,
+}
+
+impl Capture {
+    fn new(limits: OutputLimits) -> Self {
+        let (head_lines, tail_lines) =
+            if limits.head_lines().is_some() || limits.tail_lines().is_some() {
+                (
+                    limits.head_lines().unwrap_or_default(),
+                    limits.tail_lines().unwrap_or_default(),
+                )
+            } else {
+                (usize::MAX, usize::MAX)
+            };
+        let max_bytes = limits.max_bytes().unwrap_or(usize::MAX);
+        // The bytes are shared evenly, unless one end keeps no lines at all
+        let head_bytes = if head_lines == 0 {
+            0
+        } else if tail_lines == 0 {
+            max_bytes
+        } else {
+            max_bytes / 2
+        };
+        Self {
+            head_lines,
+            head_bytes,
+            tail_lines,
+            tail_bytes: max_bytes - head_bytes,
+            head: vec![],
+            head_used: 0,
+            head_full: false,
+            tail: VecDeque::new(),
+            tail_used: 0,
+            dropped: 0,
+        }
+    }
+
+    fn push(&mut self, line: OutputLine) {
+        let len = line.line().len();
+        if !self.head_full
+            && self.head.len() < self.head_lines
+            && self.head_used + len <= self.head_bytes
+        {
+            self.head_used += len;
+            self.head.push(line);
+            return;
+        }
+
+        self.head_full = true;
+        self.tail_used += len;
+        self.tail.push_back(line);
+        while self.tail.len() > self.tail_lines || self.tail_used > self.tail_bytes {
+            if let Some(dropped) = self.tail.pop_front() {
+                self.tail_used -= dropped.line().len();
+                self.dropped += 1;
+            }
+        }
+    }
+
+    // The kept lines and the number dropped between the head and the tail
+    fn finish(self) -> (Vec<OutputLine>, usize) {
+        let mut lines = self.head;
+        lines.extend(self.tail);
+        (lines, self.dropped)
+    }
+}
+
+// Merge the lines of two streams by when they were written, stdout first when
+// two lines were written at the same time
+fn merge(stdout: Vec<OutputLine>, stderr: Vec<OutputLine>) -> Vec<OutputLine> {
+    let mut merged = Vec::with_capacity(stdout.len() + stderr.len());
+    let mut stdout = stdout.into_iter().peekable();
+    let mut stderr = stderr.into_iter().peekable();
+    loop {
+        let next = match (stdout.peek(), stderr.peek()) {
+            (Some(out), Some(err)) if err.offset() < out.offset() => stderr.next(),
+            (Some(_), _) => stdout.next(),
+            (None, _) => stderr.next(),
+        };
+        match next {
+            Some(line) => merged.push(line),
+            None => break,
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod test {
+    use super::{merge, Capture};
+    use anyhow::Result;
+    use pudlib::{OutputLimits, OutputLine, Stream};
+    use std::time::Duration;
+
+    fn capture(limits: &str, count: u64) -> Result<(Vec<String>, usize)> {
+        let limits: OutputLimits = serde_json::from_str(limits)?;
+        let mut capture = Capture::new(limits);
+        for i in 0..count {
+            capture.push(OutputLine::new(
+                Duration::from_millis(i),
+                Stream::Stdout,
+                format!("line {i}"),
+            ));
+        }
+        let (lines, dropped) = capture.finish();
+        Ok((
+            lines.iter().map(|line| line.line().clone()).collect(),
+            dropped,
+        ))
+    }
+
+    #[test]
+    fn unlimited_keeps_everything() -> Result<()> {
+        let (lines, dropped) = capture("{}", 1000)?;
+        assert_eq!(lines.len(), 1000);
+        assert_eq!(dropped, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_head_and_tail() -> Result<()> {
+        let (lines, dropped) = capture(r#"{"head_lines":2,"tail_lines":3}"#, 10)?;
+        assert_eq!(dropped, 5);
+        assert_eq!(
+            lines,
+            vec!["line 0", "line 1", "line 7", "line 8", "line 9"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn short_output_is_not_truncated() -> Result<()> {
+        let (lines, dropped) = capture(r#"{"head_lines":2,"tail_lines":3}"#, 4)?;
+        assert_eq!(dropped, 0);
+        assert_eq!(lines, vec!["line 0", "line 1", "line 2", "line 3"]);
+        Ok(())
+    }
+
+    #[test]
+    fn tail_only() -> Result<()> {
+        let (lines, dropped) = capture(r#"{"tail_lines":1}"#, 3)?;
+        assert_eq!(dropped, 2);
+        assert_eq!(lines, vec!["line 2"]);
+        Ok(())
+    }
+
+    #[test]
+    fn byte_limit_is_shared() -> Result<()> {
+        // each line is 6 bytes, so 3 fit in each half of 36 bytes
+        let (lines, dropped) = capture(r#"{"max_bytes":36}"#, 10)?;
+        assert_eq!(dropped, 4);
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[0], "line 0");
+        assert_eq!(lines[2], "line 2");
+        assert_eq!(lines[3], "line 7");
+        assert_eq!(lines[5], "line 9");
+        Ok(())
+    }
+
+    #[test]
+    fn merges_streams_by_offset() {
+        let line =
+            |millis, stream, line| OutputLine::new(Duration::from_millis(millis), stream, line);
+        let stdout = vec![
+            line(1, Stream::Stdout, "a"),
+            line(3, Stream::Stdout, "c"),
+            line(5, Stream::Stdout, "e"),
+        ];
+        let stderr = vec![line(2, Stream::Stderr, "b"), line(5, Stream::Stderr, "f")];
+        let merged: Vec<String> = merge(stdout, stderr)
+            .into_iter()
+            .map(|line| line.line().clone())
+            .collect();
+        assert_eq!(merged, vec!["a", "b", "c", "e", "f"]);
+    }
+}
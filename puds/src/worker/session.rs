@@ -8,8 +8,11 @@
 
 //! Worker Session
 
-use super::message::{Connect, Disconnect};
-use crate::{model::doc::Job, server::Server, utils::handle_server_to_client};
+use super::{
+    message::{Connect, Disconnect},
+    output::JobOutput,
+};
+use crate::{model::doc::Job, server::Server, store::JobStore, utils::handle_server_to_client};
 use actix::{
     fut, Actor, ActorContext, ActorFutureExt, Addr, AsyncContext, ContextFutureSpawner, Handler,
     Running, StreamHandler, WrapFuture,
@@ -21,12 +24,12 @@ use anyhow::Result;
 use bincode::deserialize;
 use bytestring::ByteString;
 use pudlib::{
-    parse_ts_ping, send_ts_ping, ServerToWorkerClient, WorkerClientToWorkerSession,
-    WorkerSessionToServer,
+    parse_ts_ping, send_ts_ping, JobEvent, KillReason, OutputLimits, OutputLine,
+    ServerToWorkerClient, Stream, WorkerClientToWorkerSession, WorkerSessionToServer,
 };
-use ruarango::{coll, doc, Collection, Connection, DocMetaResult, Document};
 use std::{
     collections::HashMap,
+    sync::Arc,
     time::{Duration, Instant},
 };
 use time::OffsetDateTime;
@@ -57,11 +60,14 @@ pub(crate) struct Session {
     cont_bytes: BytesMut,
     /// The start instant of this session
     origin: Instant,
-    /// A connection to the database
-    conn: Connection,
+    /// The job store
+    store: Arc<dyn JobStore>,
     /// Current jobs docs
     #[builder(default = HashMap::new())]
     jobs: HashMap<Uuid, Job>,
+    /// The output captured so far for the current jobs
+    #[builder(default = HashMap::new())]
+    outputs: HashMap<Uuid, JobOutput>,
 }
 
 impl Session {
@@ -127,41 +133,84 @@ impl Session {
                         name: self.name.clone(),
                     });
                 }
-                WorkerClientToWorkerSession::JobStart { id, name } => {
-                    info!("job '{name}' has started");
-                    let job = Job::new(self.id, &self.name, id, &name);
-                    let _old = self.jobs.insert(id, job);
-                }
-                WorkerClientToWorkerSession::JobEnd { id, name } => {
+                WorkerClientToWorkerSession::JobStart {
+                    id,
+                    name,
+                    requested_by,
+                    output,
+                    start_time,
+                } => self.start_job(id, &name, requested_by, output, start_time),
+                WorkerClientToWorkerSession::JobEnd { id, name, end_time } => {
                     info!("job '{name}' has ended");
-                    if let Some(mut job) = self.jobs.remove(&id) {
-                        _ = job.set_end_time(OffsetDateTime::now_utc());
-                        self.store_job_document(ctx, job);
-                    }
+                    self.forward_job_event(id, JobEvent::Ended);
+                    self.end_job(ctx, id, end_time);
                 }
-                WorkerClientToWorkerSession::Stdout { id, line } => {
+                WorkerClientToWorkerSession::Skipped {
+                    id,
+                    name,
+                    start_time,
+                } => {
+                    info!("job '{name}' was skipped, its previous run is still going");
+                    let mut job = Job::new(self.id, &self.name, id, &name);
+                    _ = job
+                        .set_skipped(true)
+                        .set_start_time(start_time)
+                        .set_end_time(start_time);
+                    self.store_job_document(ctx, job);
+                }
+                WorkerClientToWorkerSession::Stdout {
+                    id,
+                    offset,
+                    line,
+                    base64,
+                } => self.output(id, offset, Stream::Stdout, line, base64),
+                WorkerClientToWorkerSession::Stderr {
+                    id,
+                    offset,
+                    line,
+                    base64,
+                } => self.output(id, offset, Stream::Stderr, line, base64),
+                WorkerClientToWorkerSession::Exit { id, exit } => {
+                    self.forward_job_event(id, JobEvent::Exit(exit));
                     if let Some(job) = self.jobs.get_mut(&id) {
-                        job.stdout_mut().push(line);
+                        _ = job.set_status(Some(exit.status()));
+                        _ = job.set_exit(Some(exit));
                     }
                 }
-                WorkerClientToWorkerSession::Stderr { id, line } => {
+                WorkerClientToWorkerSession::Killed { id, reason } => {
                     if let Some(job) = self.jobs.get_mut(&id) {
-                        job.stderr_mut().push(line);
+                        _ = job.set_killed(Some(reason));
                     }
                 }
-                WorkerClientToWorkerSession::Status { id, code } => {
+                WorkerClientToWorkerSession::Cancelled { id, manager_id } => {
                     if let Some(job) = self.jobs.get_mut(&id) {
-                        _ = job.set_status(code);
+                        info!("job '{}' was cancelled by {manager_id}", job.name());
+                        _ = job.set_killed(Some(KillReason::Cancelled));
+                        _ = job.set_cancelled_by(Some(manager_id));
                     }
                 }
+                WorkerClientToWorkerSession::CancelResult {
+                    manager_id,
+                    id,
+                    cancelled,
+                } => {
+                    self.addr.do_send(WorkerSessionToServer::CancelResult {
+                        manager_id,
+                        worker: self.name.clone(),
+                        id,
+                        cancelled,
+                    });
+                }
                 WorkerClientToWorkerSession::Schedules {
                     manager_id,
                     schedules,
+                    timezone,
                 } => {
                     self.addr.do_send(WorkerSessionToServer::Schedules {
                         manager_id,
                         name: self.name.clone(),
                         schedules,
+                        timezone,
                     });
                 }
             },
@@ -169,6 +218,20 @@ impl Session {
         }
     }
 
+    // Forward an event for an in-flight job to the server, so it can be
+    // streamed to the manager that asked for the job and any that follow it
+    fn forward_job_event(&self, id: Uuid, event: JobEvent) {
+        if let Some(job) = self.jobs.get(&id) {
+            self.addr.do_send(WorkerSessionToServer::JobEvent {
+                requested_by: *job.requested_by(),
+                worker: self.name.clone(),
+                id,
+                name: job.name().clone(),
+                event,
+            });
+        }
+    }
+
     #[allow(clippy::unused_self)]
     fn handle_close(&mut self, ctx: &mut WebsocketContext<Self>, reason: Option<CloseReason>) {
         debug!("handling close message");
@@ -199,51 +262,82 @@ impl Session {
     }
 
     fn create_collection(&self, ctx: &mut WebsocketContext<Self>) {
-        let conn_c = self.conn.clone();
-        let name_c = self.name.clone();
+        let create = self.store.create_collection(&self.name);
         _ = ctx.spawn(
             async move {
-                if let Err(e) = Collection::collection(&conn_c, &name_c).await {
-                    debug!("collection not found: {e}");
-                    if let Ok(coll_config) =
-                        coll::input::ConfigBuilder::default().name(&name_c).build()
-                    {
-                        match Collection::create(&conn_c, &coll_config).await {
-                            Ok(_) => info!("collection '{name_c}' created successfully!"),
-                            Err(e) => error!("{e}"),
-                        }
-                    }
+                if let Err(e) = create.await {
+                    error!("{e}");
                 }
             }
             .into_actor(self),
         );
     }
 
-    fn store_job_document(&self, ctx: &mut WebsocketContext<Self>, job: Job) {
-        if let Ok(config) = doc::input::CreateConfigBuilder::default()
-            .collection(&self.name)
-            .document(job)
-            .build()
-        {
-            let conn_c = self.conn.clone();
-            _ = ctx.spawn(
-                async move {
-                    debug!("creating job document");
-                    let doc_meta_res: DocMetaResult<(), ()> =
-                        Document::create(&conn_c, config).await;
-                    match doc_meta_res {
-                        Ok(doc_meta_either) => {
-                            if let Some(doc_meta) = doc_meta_either.right() {
-                                info!("job document created: {}", doc_meta.id());
-                            }
-                        }
-                        Err(e) => error!("{e}"),
-                    }
+    // Stream an output line of an in-flight job and keep it for its document
+    fn output(&mut self, id: Uuid, offset: Duration, stream: Stream, line: String, base64: bool) {
+        let mut line = OutputLine::new(offset, stream, line);
+        _ = line.set_base64(base64);
+        self.forward_job_event(id, JobEvent::Output(line.clone()));
+        if let Some(output) = self.outputs.get_mut(&id) {
+            output.push(line);
+        }
+    }
+
+    // Start the document of a job, with somewhere to capture its output
+    fn start_job(
+        &mut self,
+        id: Uuid,
+        name: &str,
+        requested_by: Option<Uuid>,
+        output: OutputLimits,
+        start_time: OffsetDateTime,
+    ) {
+        info!("job '{name}' has started");
+        let mut job = Job::new(self.id, self.name.as_str(), id, name);
+        _ = job
+            .set_requested_by(requested_by)
+            .set_start_time(start_time);
+        let _old = self.jobs.insert(id, job);
+        let _old = self.outputs.insert(id, JobOutput::new(output));
+        self.forward_job_event(id, JobEvent::Started(start_time));
+    }
+
+    // Finish the document of an ended job with its captured output, and store it
+    fn end_job(&mut self, ctx: &mut WebsocketContext<Self>, id: Uuid, end_time: OffsetDateTime) {
+        if let Some(mut job) = self.jobs.remove(&id) {
+            _ = job.set_end_time(end_time);
+            if let Some(output) = self.outputs.remove(&id) {
+                output.finish(&mut job);
+                if *job.truncated() {
+                    info!(
+                        "job '{}' output truncated, {} stdout and {} stderr line(s) dropped",
+                        job.name(),
+                        job.stdout_dropped(),
+                        job.stderr_dropped()
+                    );
                 }
-                .into_actor(self),
-            );
+            }
+            self.store_job_document(ctx, job);
         }
     }
+
+    // Store a job document, then let the worker know it can drop the events
+    // it spooled for the job
+    fn store_job_document(&self, ctx: &mut WebsocketContext<Self>, job: Job) {
+        let id = *job.id();
+        let insert = self.store.insert_job(&self.name, job);
+        _ = ctx.spawn(
+            async move {
+                debug!("creating job document");
+                insert.await
+            }
+            .into_actor(self)
+            .map(move |res, _act, ctx| match res {
+                Ok(()) => handle_server_to_client(ServerToWorkerClient::JobStored { id }, ctx),
+                Err(e) => error!("{e}"),
+            }),
+        );
+    }
 }
 
 impl Actor for Session {
@@ -285,6 +379,12 @@ impl Actor for Session {
 
     fn stopping(&mut self, _: &mut Self::Context) -> Running {
         info!("worker session stopping");
+        for job in self.jobs.values() {
+            info!(
+                "job '{}' is unfinished, the worker will replay it",
+                job.name()
+            );
+        }
         self.addr.do_send(Disconnect::builder().id(self.id).build());
         Running::Stop
     }
@@ -8,6 +8,24 @@
 
 // The worker actix actor
 
+mod output;
+mod overlap;
+mod process;
+mod realtime;
+mod running;
+
+pub(crate) use self::{overlap::RunSlots, running::RunningJobs};
+
+use self::{
+    output::forward_output,
+    overlap::{Admission, RunSlot},
+    process::{build_command, kill_job, kill_remaining, terminate},
+    realtime::{sleep_until_next, RealtimeSchedule},
+};
+use crate::{
+    constants::{DEFAULT_GRACE_PERIOD, OUTPUT_GRACE_PERIOD},
+    state::{EventSender, Spool, Timestamps},
+};
 use actix::{
     io::{SinkWrite, WriteHandler},
     Actor, ActorContext, AsyncContext, Context, Handler, SpawnHandle, StreamHandler, System,
@@ -23,24 +41,25 @@ use bincode::{deserialize, serialize};
 use bytes::{Bytes, BytesMut};
 use futures::stream::SplitSink;
 use pudlib::{
-    parse_calendar, parse_ts_ping, send_ts_ping, Command, Realtime, Schedule, ServerToWorkerClient,
+    parse_calendar_seeded, parse_cron, parse_ts_ping, send_ts_ping, Command, Cron, JobExit,
+    KillReason, OutputLimits, Overlap, Realtime, Schedule, ServerToWorkerClient, Stream,
     WorkerClientToWorkerSession,
 };
+#[cfg(unix)]
+use std::os::unix::process::ExitStatusExt;
 use std::{
-    collections::{BTreeMap, HashMap, VecDeque},
-    env,
-    io::{BufRead, BufReader},
-    process::Stdio,
+    collections::{BTreeMap, VecDeque},
+    mem,
+    process::{Child, ExitStatus, Stdio},
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc, Condvar, Mutex,
     },
-    thread,
+    thread::{self, JoinHandle},
     time::{Duration, Instant},
 };
 use time::OffsetDateTime;
-use tokio::sync::mpsc::UnboundedSender;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 use typed_builder::TypedBuilder;
 use uuid::Uuid;
 
@@ -56,8 +75,11 @@ pub(crate) struct Worker {
     hb: Instant,
     // The addr used to send messages back to the worker session
     addr: SinkWrite<Message, SplitSink<Framed<BoxedSocket, Codec>, Message>>,
-    // the sender for the worker client to worker session messages
-    tx: UnboundedSender<WorkerClientToWorkerSession>,
+    // the sender for the worker client to worker session messages, which
+    // spools the job events
+    tx: EventSender,
+    // the journal of job events the server hasn't acknowledged yet
+    spool: Spool,
     // handle to the stdout queue future
     #[builder(default = Arc::new(Mutex::new(None)))]
     stdout_handle: Arc<Mutex<Option<SpawnHandle>>>,
@@ -83,14 +105,32 @@ pub(crate) struct Worker {
     #[builder(default = Vec::new())]
     schedules: Vec<Schedule>,
     // The realtime schedules
-    #[builder(default = HashMap::new())]
-    rt: HashMap<Realtime, Vec<String>>,
+    #[builder(default = Vec::new())]
+    rt: Vec<RealtimeSchedule>,
+    // handle to the realtime monitor future
+    #[builder(default)]
+    rt_handle: Option<SpawnHandle>,
+    // The last fire times of the persistent realtime schedules
+    timestamps: Timestamps,
+    // The default timezone for realtime schedules
+    timezone: Option<String>,
+    // The name of this worker, used to seed randomized calendar fields
+    name: String,
+    // The @reboot commands waiting to run
+    #[builder(default = Vec::new())]
+    reboot_cmds: Vec<String>,
+    // Have the @reboot commands been run by this process
+    rebooted: Arc<AtomicBool>,
     // Current futures handles
     #[builder(default = Vec::new())]
     fut_handles: Vec<SpawnHandle>,
-    // Running condvar for stopping child process
-    #[builder(default = Arc::new((Mutex::new(false), Condvar::new())))]
+    // Running condvar for stopping child process.  This, the running jobs and
+    // the run slots outlive a connection, as the jobs do.
     running_pair: Arc<(Mutex<bool>, Condvar)>,
+    // The jobs currently running, so they can be cancelled
+    running_jobs: RunningJobs,
+    // The run slot of each schedule, so its overlap policy can be enforced
+    run_slots: RunSlots,
 }
 
 impl Worker {
@@ -124,30 +164,126 @@ impl Worker {
         });
     }
 
+    // Sleep until the earliest realtime schedule is due, fire everything that
+    // is due, and go back to sleep
     fn start_rt_monitor(&mut self, ctx: &mut Context<Self>) {
-        debug!("starting realtime schedule monitor");
-        let rt_handle = ctx.run_interval(Duration::from_secs(1), move |act, _ctx| {
-            let now = OffsetDateTime::now_utc();
-            for (rt, cmds) in &act.rt {
-                if rt.should_run(now) {
-                    let cmds_thread = cmds.clone();
-                    let commands_thread = act.commands.clone();
-                    let tx = act.tx.clone();
-                    let running_pair_c = act.running_pair.clone();
-
-                    // Run the long running commands in a separate thread
-                    let _b = thread::spawn(move || {
-                        // Run the commands sequentially
-                        for cmd_name in &cmds_thread {
-                            if let Some(cmd) = commands_thread.get(cmd_name) {
-                                run_cmd(cmd_name, cmd.cmd(), &running_pair_c, &tx);
-                            }
-                        }
-                    });
+        let sleep = sleep_until_next(&self.rt, OffsetDateTime::now_utc());
+        debug!(
+            "realtime schedule monitor sleeping {}s",
+            sleep.as_secs_f64()
+        );
+        self.rt_handle = Some(ctx.run_later(sleep, move |act, ctx| {
+            act.rt_tick();
+            act.start_rt_monitor(ctx);
+        }));
+    }
+
+    fn rt_tick(&mut self) {
+        let now = OffsetDateTime::now_utc();
+        let mut due = Vec::new();
+        for schedule in &mut self.rt {
+            if let Some(occurrence) = schedule.poll(now) {
+                due.push((schedule.clone(), occurrence));
+            }
+        }
+        for (schedule, occurrence) in &due {
+            self.fire(schedule, *occurrence);
+        }
+    }
+
+    // A persistent schedule's fire is recorded once its commands have all
+    // succeeded, so a failed or skipped run is caught up on later
+    fn fire(&self, schedule: &RealtimeSchedule, at: OffsetDateTime) {
+        let fired = schedule.persistent.then(|| Fired {
+            timestamps: self.timestamps.clone(),
+            key: schedule.key.clone(),
+            at,
+        });
+        self.run_cmds(&schedule.cmds, &schedule.slot, fired);
+    }
+
+    // Run any persistent schedule that should have fired while this worker was
+    // stopped or disconnected
+    fn catch_up_persistent(&mut self) {
+        let now = OffsetDateTime::now_utc();
+        let persistent: Vec<RealtimeSchedule> = self
+            .rt
+            .iter()
+            .filter(|schedule| schedule.persistent)
+            .cloned()
+            .collect();
+        for schedule in &persistent {
+            if let Some(last) = self.timestamps.last(&schedule.key) {
+                if let Some(missed) = schedule
+                    .realtime
+                    .next_after(last)
+                    .filter(|next| *next < now)
+                {
+                    info!("'{}' missed a run at {missed}, running now", schedule.key);
+                    self.fire(schedule, now);
                 }
+            } else if let Err(e) = self.timestamps.record(&schedule.key, now) {
+                // never fired, start tracking from now
+                error!("unable to record fire time: {e:?}");
+            }
+        }
+    }
+
+    fn run_cmds(&self, cmds: &[String], slot: &RunSlot, fired: Option<Fired>) {
+        let cmds_thread = cmds.to_vec();
+        let commands_thread = self.commands.clone();
+        let slot_thread = slot.clone();
+        let tx = self.tx.clone();
+        let running_pair_c = self.running_pair.clone();
+        let running_jobs_c = self.running_jobs.clone();
+
+        // Run the long running commands in a separate thread
+        let _b = thread::spawn(move || {
+            run_scheduled(
+                &cmds_thread,
+                &commands_thread,
+                &slot_thread,
+                &running_pair_c,
+                &running_jobs_c,
+                fired.as_ref(),
+                &tx,
+            );
+        });
+    }
+
+    // Run a command now, at the request of a manager.  The overlap policy of
+    // any schedule using the command does not apply.
+    fn run_requested(&self, name: String, manager_id: Uuid) {
+        let command = self.commands.get(&name).cloned();
+        let tx = self.tx.clone();
+        let running_pair_c = self.running_pair.clone();
+        let running_jobs_c = self.running_jobs.clone();
+
+        let _b = thread::spawn(move || {
+            if let Some(command) = command {
+                let cancel = AtomicBool::new(false);
+                _ = run_cmd(
+                    &name,
+                    &command,
+                    &running_pair_c,
+                    &running_jobs_c,
+                    &cancel,
+                    Some(manager_id),
+                    &tx,
+                );
+            } else {
+                error!("'{name}' is not a known command");
+                let command_id = Uuid::new_v4();
+                record_job_start(
+                    command_id,
+                    &name,
+                    Some(manager_id),
+                    OutputLimits::default(),
+                    &tx,
+                );
+                record_job_end(command_id, &name, &tx);
             }
         });
-        self.fut_handles.push(rt_handle);
     }
 
     fn queue_monitor(&mut self, ctx: &mut Context<Self>) {
@@ -216,6 +352,9 @@ impl Worker {
                         Err(poisoned) => poisoned.into_inner(),
                     };
                     *running = true;
+                    drop(running);
+                    self.catch_up_persistent();
+                    self.run_reboot_cmds();
                     info!("worker initialization complete");
                 }
                 ServerToWorkerClient::Reload => {
@@ -228,12 +367,38 @@ impl Worker {
                     if let Ok(msg) = serialize(&WorkerClientToWorkerSession::Schedules {
                         manager_id,
                         schedules: self.schedules.clone(),
+                        timezone: self.timezone.clone(),
                     }) {
                         if let Err(e) = self.addr.write(Message::Binary(Bytes::from(msg))) {
                             error!("unable to write schedules message: {e:?}");
                         }
                     }
                 }
+                ServerToWorkerClient::Run { manager_id, name } => {
+                    info!("'{name}' has been requested to run now");
+                    self.run_requested(name, manager_id);
+                }
+                ServerToWorkerClient::Cancel { manager_id, id } => {
+                    let cancelled = self.running_jobs.cancel(&id, manager_id);
+                    if cancelled {
+                        info!("job {id} has been cancelled by {manager_id}");
+                    } else {
+                        warn!("job {id} cannot be cancelled, it is not running");
+                    }
+                    if let Err(e) = self.tx.send(WorkerClientToWorkerSession::CancelResult {
+                        manager_id,
+                        id,
+                        cancelled,
+                    }) {
+                        error!("{e}");
+                    }
+                }
+                ServerToWorkerClient::JobStored { id } => {
+                    debug!("job {id} has been stored");
+                    if let Err(e) = self.spool.acknowledge(&id) {
+                        error!("{e}");
+                    }
+                }
             }
         }
     }
@@ -297,6 +462,11 @@ impl Worker {
                 debug!("future cancelled successfully");
             }
         }
+        if let Some(handle) = self.rt_handle.take() {
+            if ctx.cancel_future(handle) {
+                debug!("realtime monitor cancelled successfully");
+            }
+        }
         self.rt.clear();
     }
 
@@ -310,15 +480,41 @@ impl Worker {
                     on_boot_sec,
                     on_unit_active_sec,
                     cmds,
-                } => self.launch_monotonic(ctx, *on_boot_sec, *on_unit_active_sec, cmds),
+                    overlap,
+                } => {
+                    let key = format!(
+                        "{}s+{}s|{}",
+                        on_boot_sec.as_secs_f64(),
+                        on_unit_active_sec.as_secs_f64(),
+                        cmds.join(",")
+                    );
+                    let slot = self.run_slots.slot(&key, *overlap);
+                    self.launch_monotonic(ctx, *on_boot_sec, *on_unit_active_sec, cmds, slot);
+                }
                 Schedule::Realtime {
                     on_calendar,
                     persistent,
                     cmds,
-                } => {
-                    has_realtime = true;
-                    self.store_realtime(on_calendar, *persistent, cmds);
-                }
+                    overlap,
+                } => match parse_calendar_seeded(on_calendar, &self.name) {
+                    Ok(realtime) => {
+                        has_realtime = true;
+                        self.store_realtime(on_calendar, realtime, *persistent, cmds, *overlap);
+                    }
+                    Err(e) => error!("{e}"),
+                },
+                Schedule::Cron {
+                    expr,
+                    cmds,
+                    overlap,
+                } => match parse_cron(expr) {
+                    Ok(Cron::Reboot) => self.reboot_cmds.extend(cmds.iter().cloned()),
+                    Ok(Cron::Calendar(realtime)) => {
+                        has_realtime = true;
+                        self.store_realtime(expr, *realtime, false, cmds, *overlap);
+                    }
+                    Err(e) => error!("{e}"),
+                },
             }
         }
 
@@ -333,6 +529,7 @@ impl Worker {
         on_boot_sec: Duration,
         on_unit_active_sec: Duration,
         cmds: &[String],
+        slot: RunSlot,
     ) {
         debug!(
             "launching monotonic schedule in {}s, re-running every {}s",
@@ -341,56 +538,55 @@ impl Worker {
         );
         // clone everything to move into the initial run later future
         let cmds_later = cmds.to_owned();
-        let commands_later = self.commands.clone();
-        let tx_later = self.tx.clone();
-        let running_pair_later = self.running_pair.clone();
 
         let later_handle = ctx.run_later(on_boot_sec, move |act, ctx| {
             // clone everything to move into the interval future
             let cmds_interval = cmds_later.clone();
-            let commands_interval = commands_later.clone();
+            let slot_interval = slot.clone();
 
             let mono_handle = ctx.run_interval(on_unit_active_sec, move |act, _ctx| {
-                // clone everything to move into the command thread
-                let cmds_thread = cmds_interval.clone();
-                let commands_thread = commands_interval.clone();
-                let tx_thread = act.tx.clone();
-                let running_pair_c = act.running_pair.clone();
-
-                // Run the long running commands in a separate thread
-                let _b = thread::spawn(move || {
-                    // Run the commands sequentially
-                    for cmd_name in &cmds_thread {
-                        if let Some(cmd) = commands_thread.get(cmd_name) {
-                            run_cmd(cmd_name, cmd.cmd(), &running_pair_c, &tx_thread);
-                        }
-                    }
-                });
+                act.run_cmds(&cmds_interval, &slot_interval, None);
             });
 
             act.fut_handles.push(mono_handle);
-
-            // Run the long running commands in a separate thread
-            let _b = thread::spawn(move || {
-                // Run the commands sequentially
-                for cmd_name in &cmds_later {
-                    if let Some(cmd) = commands_later.get(cmd_name) {
-                        run_cmd(cmd_name, cmd.cmd(), &running_pair_later, &tx_later);
-                    }
-                }
-            });
+            act.run_cmds(&cmds_later, &slot, None);
         });
 
         self.fut_handles.push(later_handle);
     }
 
-    fn store_realtime(&mut self, on_calendar: &str, _persistent: bool, cmds: &[String]) {
-        match parse_calendar(on_calendar) {
-            Ok(rt) => {
-                debug!("adding realtime schedule {rt:?}");
-                let _prev = self.rt.insert(rt, cmds.to_vec());
-            }
-            Err(e) => error!("{e}"),
+    fn store_realtime(
+        &mut self,
+        calendar: &str,
+        realtime: Realtime,
+        persistent: bool,
+        cmds: &[String],
+        overlap: Overlap,
+    ) {
+        let realtime = match &self.timezone {
+            Some(timezone) => realtime.with_default_timezone(timezone),
+            None => realtime,
+        };
+        debug!("adding realtime schedule {realtime:?}");
+        let key = format!("{calendar}|{}", cmds.join(","));
+        let slot = self.run_slots.slot(&key, overlap);
+        self.rt.push(RealtimeSchedule::new(
+            key,
+            realtime,
+            persistent,
+            cmds.to_vec(),
+            slot,
+            OffsetDateTime::now_utc(),
+        ));
+    }
+
+    // @reboot commands run on the first initialization of this process only,
+    // not on every reconnect or reload
+    fn run_reboot_cmds(&mut self) {
+        let cmds = mem::take(&mut self.reboot_cmds);
+        if !self.rebooted.swap(true, Ordering::SeqCst) && !cmds.is_empty() {
+            info!("running {} @reboot commands", cmds.len());
+            self.run_cmds(&cmds, &RunSlot::new(Overlap::Allow), None);
         }
     }
 
@@ -464,147 +660,353 @@ impl Handler<WorkerClientToWorkerSession> for Worker {
     }
 }
 
+// A fire of a persistent schedule, to record once its commands succeed
+struct Fired {
+    timestamps: Timestamps,
+    key: String,
+    at: OffsetDateTime,
+}
+
+impl Fired {
+    fn record(&self) {
+        if let Err(e) = self.timestamps.record(&self.key, self.at) {
+            error!("unable to record fire time: {e:?}");
+        }
+    }
+}
+
+// Run the commands for one fire of a schedule, honoring its overlap policy
+fn run_scheduled(
+    cmds: &[String],
+    commands: &BTreeMap<String, Command>,
+    slot: &RunSlot,
+    running_pair: &Arc<(Mutex<bool>, Condvar)>,
+    running_jobs: &RunningJobs,
+    fired: Option<&Fired>,
+    tx: &EventSender,
+) {
+    let mut cancel = match slot.admit() {
+        Admission::Run(cancel) => cancel,
+        Admission::Skip => {
+            for cmd_name in cmds {
+                info!("'{cmd_name}' is still running, skipping");
+                record_job_skipped(cmd_name, tx);
+            }
+            return;
+        }
+        Admission::Queued => {
+            debug!("previous run still going, queued another");
+            return;
+        }
+        Admission::Superseded => {
+            debug!("replaced by a later run before starting");
+            return;
+        }
+    };
+
+    loop {
+        // Run the commands sequentially
+        let mut succeeded = true;
+        for cmd_name in cmds {
+            if cancel.load(Ordering::SeqCst) {
+                succeeded = false;
+                break;
+            }
+            if let Some(cmd) = commands.get(cmd_name) {
+                succeeded &= run_cmd(cmd_name, cmd, running_pair, running_jobs, &cancel, None, tx);
+            } else {
+                succeeded = false;
+            }
+        }
+        if let Some(fired) = fired.filter(|_| succeeded) {
+            fired.record();
+        }
+        match slot.finish() {
+            Some(next) => cancel = next,
+            None => break,
+        }
+    }
+}
+
 #[allow(clippy::too_many_lines, clippy::single_match_else)]
 fn run_cmd(
     name: &str,
-    command: &str,
+    command: &Command,
     running_pair: &Arc<(Mutex<bool>, Condvar)>,
-    tx: &UnboundedSender<WorkerClientToWorkerSession>,
-) {
-    if let Some(shell_path) = env::var_os("SHELL") {
-        let command_id = Uuid::new_v4();
-        record_job_start(command_id, name, tx);
-
-        let shell = shell_path.to_string_lossy().to_string();
-        let mut cmd = std::process::Command::new(shell);
-        _ = cmd.arg("-c");
-        _ = cmd.arg(command);
+    running_jobs: &RunningJobs,
+    cancel: &AtomicBool,
+    requested_by: Option<Uuid>,
+    tx: &EventSender,
+) -> bool {
+    let command_id = Uuid::new_v4();
+    let cancelled_by = running_jobs.register(command_id);
+    record_job_start(command_id, name, requested_by, *command.output(), tx);
+
+    let job_start = Instant::now();
+    let mut succeeded = false;
+    let spawned = build_command(command).and_then(|mut cmd| {
         _ = cmd.stdout(Stdio::piped());
         _ = cmd.stderr(Stdio::piped());
-
-        match cmd.spawn() {
-            Ok(mut child) => {
-                let _stdout_handle_opt = match child.stdout.take() {
-                    Some(child_stdout) => {
-                        let tx_stdout = tx.clone();
-                        let stdout_handle = thread::spawn(move || {
-                            let stdout_reader = BufReader::new(child_stdout);
-                            for line in stdout_reader.lines().map_while(Result::ok) {
-                                let stdout_m = WorkerClientToWorkerSession::Stdout {
-                                    id: command_id,
-                                    line,
-                                };
-                                if let Err(e) = tx_stdout.send(stdout_m) {
-                                    error!("{e}");
-                                }
-                            }
-                        });
-                        Some(stdout_handle)
-                    }
-                    _ => {
-                        error!("Unable to produce stdout!");
-                        None
+        cmd.spawn()
+    });
+
+    match spawned {
+        Ok(mut child) => {
+            let stdout_handle_opt = match child.stdout.take() {
+                Some(child_stdout) => {
+                    let tx_stdout = tx.clone();
+                    let encoding = *command.encoding();
+                    let stdout_handle = thread::spawn(move || {
+                        forward_output(
+                            child_stdout,
+                            Stream::Stdout,
+                            command_id,
+                            job_start,
+                            encoding,
+                            &tx_stdout,
+                        );
+                    });
+                    Some(stdout_handle)
+                }
+                _ => {
+                    error!("Unable to produce stdout!");
+                    None
+                }
+            };
+
+            let stderr_handle_opt = match child.stderr.take() {
+                Some(child_stderr) => {
+                    let tx_stderr = tx.clone();
+                    let encoding = *command.encoding();
+                    let stderr_handle = thread::spawn(move || {
+                        forward_output(
+                            child_stderr,
+                            Stream::Stderr,
+                            command_id,
+                            job_start,
+                            encoding,
+                            &tx_stderr,
+                        );
+                    });
+                    Some(stderr_handle)
+                }
+                _ => {
+                    error!("Unable to produce stderr!");
+                    None
+                }
+            };
+
+            let pair = running_pair.clone();
+            let started = Instant::now();
+            let grace_period = command.grace_period().unwrap_or(DEFAULT_GRACE_PERIOD);
+            // when the child was asked to terminate, and has it been killed
+            let mut terminated: Option<Instant> = None;
+            let mut killed = false;
+
+            loop {
+                match child.try_wait() {
+                    Ok(Some(status)) => {
+                        record_job_exit(command_id, status, terminated.is_some(), tx);
+                        succeeded = status.success() && terminated.is_none();
+                        break;
                     }
-                };
-
-                let _stderr_handle_opt = match child.stderr.take() {
-                    Some(child_stderr) => {
-                        let tx_stderr = tx.clone();
-                        let stderr_handle = thread::spawn(move || {
-                            let stderr_reader = BufReader::new(child_stderr);
-                            for line in stderr_reader.lines().map_while(Result::ok) {
-                                let stderr_m = WorkerClientToWorkerSession::Stderr {
-                                    id: command_id,
-                                    line,
-                                };
-                                if let Err(e) = tx_stderr.send(stderr_m) {
-                                    error!("{e}");
+                    Ok(None) => {
+                        match terminated {
+                            None if command
+                                .timeout()
+                                .is_some_and(|timeout| started.elapsed() >= timeout) =>
+                            {
+                                info!("'{name}' timed out, terminating");
+                                record_job_killed(command_id, KillReason::Timeout, tx);
+                                if let Err(e) = terminate(&child) {
+                                    error!("Unable to terminate child process: {e}");
                                 }
+                                terminated = Some(Instant::now());
                             }
-                        });
-                        Some(stderr_handle)
-                    }
-                    _ => {
-                        error!("Unable to produce stderr!");
-                        None
-                    }
-                };
-
-                let pair = running_pair.clone();
-
-                loop {
-                    match child.try_wait() {
-                        Ok(Some(status)) => {
-                            if let Some(code) = status.code() {
-                                info!("command result: {}", code);
-                                let status_msg = WorkerClientToWorkerSession::Status {
-                                    id: command_id,
-                                    code,
-                                };
-                                if let Err(e) = tx.send(status_msg) {
-                                    error!("{e}");
+                            Some(at) if !killed && at.elapsed() >= grace_period => {
+                                info!("'{name}' did not terminate, killing");
+                                if let Err(e) = kill_job(&mut child) {
+                                    error!("Unable to kill child process: {e}");
                                 }
+                                killed = true;
                             }
+                            _ => {}
+                        }
+                        // A newer run of the same schedule has replaced this one
+                        if cancel.load(Ordering::SeqCst) {
+                            info!("'{name}' was replaced, killing");
+                            record_job_killed(command_id, KillReason::Replaced, tx);
+                            kill_and_wait(command_id, &mut child, tx);
                             break;
                         }
-                        Ok(None) => {
-                            let (lock, cvar) = &*pair;
-                            let running = match lock.lock() {
-                                Ok(guard) => guard,
-                                Err(poisoned) => poisoned.into_inner(),
-                            };
-                            match cvar.wait_timeout(running, Duration::from_millis(500)) {
-                                Ok((res, wt_res)) => {
-                                    if wt_res.timed_out() {
-                                        debug!("timed out waiting on cvar, checking running flag");
-                                    }
-                                    // If we aren't in a running state, try to kill the child process
-                                    if !(*res) {
-                                        if let Err(e) = child.kill() {
-                                            error!("Unable to kill child process: {e}");
-                                        }
-                                        break;
-                                    }
+                        // A manager has cancelled this job
+                        if let Some(manager_id) = cancelled_by.get() {
+                            info!("'{name}' was cancelled, killing");
+                            record_job_cancelled(command_id, *manager_id, tx);
+                            kill_and_wait(command_id, &mut child, tx);
+                            break;
+                        }
+                        let (lock, cvar) = &*pair;
+                        let running = match lock.lock() {
+                            Ok(guard) => guard,
+                            Err(poisoned) => poisoned.into_inner(),
+                        };
+                        match cvar.wait_timeout(running, Duration::from_millis(500)) {
+                            Ok((res, wt_res)) => {
+                                if wt_res.timed_out() {
+                                    debug!("timed out waiting on cvar, checking running flag");
                                 }
-                                _ => {
-                                    error!("condvar wait timeout error");
+                                // If we aren't in a running state, try to kill the child process
+                                if !(*res) {
+                                    if terminated.is_none() {
+                                        record_job_killed(command_id, KillReason::Stopped, tx);
+                                    }
+                                    kill_and_wait(command_id, &mut child, tx);
+                                    break;
                                 }
                             }
+                            _ => {
+                                error!("condvar wait timeout error");
+                            }
                         }
-                        Err(e) => error!("{e}"),
                     }
+                    Err(e) => error!("{e}"),
                 }
             }
-            _ => {
-                error!("unable to spawn command");
-            }
+
+            let readers = [stdout_handle_opt, stderr_handle_opt]
+                .into_iter()
+                .flatten()
+                .collect();
+            join_readers(name, &child, readers);
+        }
+        Err(e) => {
+            error!("unable to spawn command: {e}");
+        }
+    }
+
+    running_jobs.remove(&command_id);
+    record_job_end(command_id, name, tx);
+    succeeded
+}
+
+// Wait for the output readers to reach the end of the job's output.  Anything
+// left in the job's process group that is still holding the output open once
+// the grace period is up is killed.  Readers that still don't finish, e.g.
+// because a process escaped the group, are left behind rather than blocking
+// this thread.
+fn join_readers(name: &str, child: &Child, readers: Vec<JoinHandle<()>>) {
+    let finished = |readers: &[JoinHandle<()>]| readers.iter().all(JoinHandle::is_finished);
+    let wait = |readers: &[JoinHandle<()>]| {
+        let deadline = Instant::now() + OUTPUT_GRACE_PERIOD;
+        while !finished(readers) && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(50));
         }
+        finished(readers)
+    };
 
-        record_job_end(command_id, name, tx);
-    } else {
-        error!("no shell defined!");
+    if !wait(&readers) {
+        warn!("'{name}' output is still open, killing the rest of its process group");
+        if let Err(e) = kill_remaining(child) {
+            error!("Unable to kill process group: {e}");
+        }
+        if !wait(&readers) {
+            error!("'{name}' output is still open, giving up on it");
+            return;
+        }
+    }
+    for reader in readers {
+        if reader.join().is_err() {
+            error!("'{name}' output reader panicked");
+        }
+    }
+}
+
+// Kill the child process and report how it exited
+fn kill_and_wait(command_id: Uuid, child: &mut Child, tx: &EventSender) {
+    if let Err(e) = kill_job(child) {
+        error!("Unable to kill child process: {e}");
+    }
+    match child.wait() {
+        Ok(status) => record_job_exit(command_id, status, true, tx),
+        Err(e) => error!("Unable to wait on child process: {e}"),
+    }
+}
+
+#[cfg(unix)]
+fn job_exit(status: ExitStatus, killed: bool) -> JobExit {
+    JobExit::new(status.code(), status.signal(), status.core_dumped(), killed)
+}
+
+#[cfg(windows)]
+fn job_exit(status: ExitStatus, killed: bool) -> JobExit {
+    JobExit::new(status.code(), None, false, killed)
+}
+
+fn record_job_exit(command_id: Uuid, status: ExitStatus, killed: bool, tx: &EventSender) {
+    let exit = job_exit(status, killed);
+    info!("command result: {exit}");
+    if let Err(e) = tx.send(WorkerClientToWorkerSession::Exit {
+        id: command_id,
+        exit,
+    }) {
+        error!("{e}");
+    }
+}
+
+fn record_job_killed(command_id: Uuid, reason: KillReason, tx: &EventSender) {
+    if let Err(e) = tx.send(WorkerClientToWorkerSession::Killed {
+        id: command_id,
+        reason,
+    }) {
+        error!("{e}");
+    }
+}
+
+fn record_job_cancelled(command_id: Uuid, manager_id: Uuid, tx: &EventSender) {
+    if let Err(e) = tx.send(WorkerClientToWorkerSession::Cancelled {
+        id: command_id,
+        manager_id,
+    }) {
+        error!("{e}");
+    }
+}
+
+fn record_job_skipped(name: &str, tx: &EventSender) {
+    if let Err(e) = tx.send(WorkerClientToWorkerSession::Skipped {
+        id: Uuid::new_v4(),
+        name: name.to_string(),
+        start_time: OffsetDateTime::now_utc(),
+    }) {
+        error!("{e}");
     }
 }
 
 fn record_job_start(
     command_id: Uuid,
     name: &str,
-    tx: &UnboundedSender<WorkerClientToWorkerSession>,
+    requested_by: Option<Uuid>,
+    output: OutputLimits,
+    tx: &EventSender,
 ) {
     info!("Running '{name}'");
     if let Err(e) = tx.send(WorkerClientToWorkerSession::JobStart {
         id: command_id,
         name: name.to_string(),
+        requested_by,
+        output,
+        start_time: OffsetDateTime::now_utc(),
     }) {
         error!("{e}");
     }
 }
 
-fn record_job_end(command_id: Uuid, name: &str, tx: &UnboundedSender<WorkerClientToWorkerSession>) {
+fn record_job_end(command_id: Uuid, name: &str, tx: &EventSender) {
     info!("'{name}' has ended");
     if let Err(e) = tx.send(WorkerClientToWorkerSession::JobEnd {
         id: command_id,
         name: name.to_string(),
+        end_time: OffsetDateTime::now_utc(),
     }) {
         error!("{e}");
     }
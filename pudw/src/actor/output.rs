@@ -0,0 +1,212 @@
+// Copyright (c) 2022 pud developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+//! Job output capture
+
+use crate::{constants::MAX_LINE_BYTES, state::EventSender};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use pudlib::{OutputEncoding, Stream, WorkerClientToWorkerSession};
+use std::{
+    io::{self, BufRead, BufReader, ErrorKind, Read},
+    str,
+    time::Instant,
+};
+use tracing::error;
+use uuid::Uuid;
+
+/// Send every line written to one of a job's output streams to the worker
+/// session, until the stream closes
+pub(crate) fn forward_output<R>(
+    reader: R,
+    stream: Stream,
+    id: Uuid,
+    job_start: Instant,
+    encoding: OutputEncoding,
+    tx: &EventSender,
+) where
+    R: Read,
+{
+    for chunk in Chunks::new(BufReader::new(reader), MAX_LINE_BYTES) {
+        match chunk {
+            Ok(bytes) => {
+                let offset = job_start.elapsed();
+                let (line, base64) = decode(bytes, encoding);
+                let message = match stream {
+                    Stream::Stdout => WorkerClientToWorkerSession::Stdout {
+                        id,
+                        offset,
+                        line,
+                        base64,
+                    },
+                    Stream::Stderr => WorkerClientToWorkerSession::Stderr {
+                        id,
+                        offset,
+                        line,
+                        base64,
+                    },
+                };
+                if let Err(e) = tx.send(message) {
+                    error!("{e}");
+                }
+            }
+            Err(e) => {
+                error!("Unable to read job output: {e}");
+                break;
+            }
+        }
+    }
+}
+
+// Turn a line into a string, base64 encoding it if it isn't valid UTF-8 and
+// that's what the command asks for
+fn decode(bytes: Vec<u8>, encoding: OutputEncoding) -> (String, bool) {
+    match String::from_utf8(bytes) {
+        Ok(line) => (line, false),
+        Err(e) => match encoding {
+            OutputEncoding::Lossy => (String::from_utf8_lossy(e.as_bytes()).into_owned(), false),
+            OutputEncoding::Base64 => (STANDARD.encode(e.into_bytes()), true),
+        },
+    }
+}
+
+// The lines of a reader as raw bytes, without the line ending.  A line longer
+// than `max` bytes is split into chunks of at most `max` bytes, never in the
+// middle of a UTF-8 character, so nothing is buffered without bound.
+struct Chunks<R> {
+    reader: R,
+    max: usize,
+    // the start of a character that didn't fit in the previous chunk
+    carry: Vec<u8>,
+}
+
+impl<R> Chunks<R> {
+    fn new(reader: R, max: usize) -> Self {
+        Self {
+            reader,
+            max,
+            carry: vec![],
+        }
+    }
+}
+
+impl<R> Iterator for Chunks<R>
+where
+    R: BufRead,
+{
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chunk = std::mem::take(&mut self.carry);
+        loop {
+            let available = match self.reader.fill_buf() {
+                Ok(available) => available,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Some(Err(e)),
+            };
+            if available.is_empty() {
+                return (!chunk.is_empty()).then_some(Ok(chunk));
+            }
+
+            let room = available.len().min(self.max.saturating_sub(chunk.len()));
+            // a newline straight after a full chunk still ends that chunk
+            let scan = available.len().min(room + 1);
+            if let Some(pos) = available[..scan].iter().position(|b| *b == b'\n') {
+                chunk.extend_from_slice(&available[..pos]);
+                self.reader.consume(pos + 1);
+                if chunk.last() == Some(&b'\r') {
+                    _ = chunk.pop();
+                }
+                return Some(Ok(chunk));
+            }
+            chunk.extend_from_slice(&available[..room]);
+            self.reader.consume(room);
+
+            if chunk.len() >= self.max {
+                if let Err(e) = str::from_utf8(&chunk) {
+                    // only an incomplete character at the very end is carried over
+                    if e.error_len().is_none() && e.valid_up_to() > 0 {
+                        self.carry = chunk.split_off(e.valid_up_to());
+                    }
+                }
+                return Some(Ok(chunk));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode, Chunks};
+    use anyhow::Result;
+    use pudlib::OutputEncoding;
+    use std::io::Cursor;
+
+    fn chunks(input: &[u8], max: usize) -> Result<Vec<Vec<u8>>> {
+        Ok(Chunks::new(Cursor::new(input.to_vec()), max).collect::<Result<Vec<_>, _>>()?)
+    }
+
+    #[test]
+    fn splits_lines() -> Result<()> {
+        assert_eq!(
+            chunks(b"one\r\ntwo\n\nthree", 64)?,
+            vec![b"one".to_vec(), b"two".to_vec(), vec![], b"three".to_vec()]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn reads_past_invalid_utf8() -> Result<()> {
+        let lines = chunks(b"ok\n\xff\xfe bad\nstill here\n", 64)?;
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[2], b"still here".to_vec());
+        Ok(())
+    }
+
+    #[test]
+    fn chunks_long_lines() -> Result<()> {
+        assert_eq!(
+            chunks(b"abcd\nk", 4)?,
+            vec![b"abcd".to_vec(), b"k".to_vec()]
+        );
+        let lines = chunks(b"abcdefghij\nk\n", 4)?;
+        assert_eq!(
+            lines,
+            vec![
+                b"abcd".to_vec(),
+                b"efgh".to_vec(),
+                b"ij".to_vec(),
+                b"k".to_vec()
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn chunks_never_split_a_character() -> Result<()> {
+        // 'é' is two bytes, and would straddle the first chunk
+        let lines = chunks("abcé\n".as_bytes(), 4)?;
+        assert_eq!(lines, vec![b"abc".to_vec(), "é".as_bytes().to_vec()]);
+        Ok(())
+    }
+
+    #[test]
+    fn decode_works() {
+        assert_eq!(
+            decode(b"fine".to_vec(), OutputEncoding::Base64),
+            ("fine".to_string(), false)
+        );
+        assert_eq!(
+            decode(b"bad \xff".to_vec(), OutputEncoding::Lossy),
+            ("bad \u{fffd}".to_string(), false)
+        );
+        assert_eq!(
+            decode(b"bad \xff".to_vec(), OutputEncoding::Base64),
+            ("YmFkIP8=".to_string(), true)
+        );
+    }
+}
@@ -0,0 +1,229 @@
+// Copyright (c) 2022 pud developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+// schedule overlap enforcement
+
+use pudlib::Overlap;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+};
+
+/// Tracks the run of a single schedule so its overlap policy can be enforced
+#[derive(Clone, Debug)]
+pub(crate) struct RunSlot {
+    policy: Overlap,
+    state: Arc<(Mutex<SlotState>, Condvar)>,
+}
+
+#[derive(Debug, Default)]
+struct SlotState {
+    // is a run of this schedule in progress
+    running: bool,
+    // should the schedule run again once the current run finishes
+    queued: bool,
+    // bumped by every replacing fire, so only the latest one ends up running
+    generation: u64,
+    // set to ask the current run to stop
+    cancel: Arc<AtomicBool>,
+}
+
+/// The run slot of every schedule, kept for the life of the worker so a run
+/// that is still going when the worker reconnects or reloads is still seen
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RunSlots {
+    slots: Arc<Mutex<HashMap<String, RunSlot>>>,
+}
+
+impl RunSlots {
+    /// The slot of the schedule with the given key.  A schedule that is new,
+    /// or whose policy has changed, gets a new slot.
+    pub(crate) fn slot(&self, key: &str, policy: Overlap) -> RunSlot {
+        let mut slots = match self.slots.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        slots
+            .entry(key.to_string())
+            .and_modify(|slot| {
+                if slot.policy != policy {
+                    *slot = RunSlot::new(policy);
+                }
+            })
+            .or_insert_with(|| RunSlot::new(policy))
+            .clone()
+    }
+}
+
+/// The outcome of a schedule firing
+#[derive(Debug)]
+pub(crate) enum Admission {
+    /// Run the commands, stopping early if the flag is set
+    Run(Arc<AtomicBool>),
+    /// The previous run is still going, report this fire as skipped
+    Skip,
+    /// The previous run is still going, run again once it finishes
+    Queued,
+    /// A later fire replaced this one before it could start
+    Superseded,
+}
+
+impl RunSlot {
+    pub(crate) fn new(policy: Overlap) -> Self {
+        Self {
+            policy,
+            state: Arc::new((Mutex::new(SlotState::default()), Condvar::new())),
+        }
+    }
+
+    /// Decide what to do with a fire of this schedule.
+    ///
+    /// With the `replace` policy this blocks until the previous run has been
+    /// stopped, so it should be called from the thread that runs the commands.
+    pub(crate) fn admit(&self) -> Admission {
+        if self.policy == Overlap::Allow {
+            return Admission::Run(Arc::new(AtomicBool::new(false)));
+        }
+
+        let (_, cvar) = &*self.state;
+        let mut state = self.lock();
+        if !state.running {
+            return Admission::Run(state.start());
+        }
+
+        match self.policy {
+            Overlap::Allow => Admission::Run(Arc::new(AtomicBool::new(false))),
+            Overlap::Skip => Admission::Skip,
+            Overlap::Queue => {
+                state.queued = true;
+                Admission::Queued
+            }
+            Overlap::Replace => {
+                state.generation += 1;
+                let generation = state.generation;
+                state.cancel.store(true, Ordering::SeqCst);
+                while state.running && state.generation == generation {
+                    state = match cvar.wait(state) {
+                        Ok(guard) => guard,
+                        Err(poisoned) => poisoned.into_inner(),
+                    };
+                }
+                if state.generation == generation {
+                    Admission::Run(state.start())
+                } else {
+                    Admission::Superseded
+                }
+            }
+        }
+    }
+
+    /// Mark the current run as finished, returning the stop flag for the
+    /// queued run that should start next, if any.
+    pub(crate) fn finish(&self) -> Option<Arc<AtomicBool>> {
+        if self.policy == Overlap::Allow {
+            return None;
+        }
+
+        let (_, cvar) = &*self.state;
+        let mut state = self.lock();
+        if state.queued {
+            state.queued = false;
+            Some(state.start())
+        } else {
+            state.running = false;
+            cvar.notify_all();
+            None
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SlotState> {
+        let (lock, _) = &*self.state;
+        match lock.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl SlotState {
+    fn start(&mut self) -> Arc<AtomicBool> {
+        self.running = true;
+        self.cancel = Arc::new(AtomicBool::new(false));
+        self.cancel.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Admission, RunSlot, RunSlots};
+    use pudlib::Overlap;
+    use std::{sync::atomic::Ordering, thread};
+
+    #[test]
+    fn allow_always_runs() {
+        let slot = RunSlot::new(Overlap::Allow);
+        assert!(matches!(slot.admit(), Admission::Run(_)));
+        assert!(matches!(slot.admit(), Admission::Run(_)));
+        assert!(slot.finish().is_none());
+    }
+
+    #[test]
+    fn skip_while_running() {
+        let slot = RunSlot::new(Overlap::Skip);
+        assert!(matches!(slot.admit(), Admission::Run(_)));
+        assert!(matches!(slot.admit(), Admission::Skip));
+        assert!(slot.finish().is_none());
+        assert!(matches!(slot.admit(), Admission::Run(_)));
+    }
+
+    #[test]
+    fn queue_runs_once_more() {
+        let slot = RunSlot::new(Overlap::Queue);
+        assert!(matches!(slot.admit(), Admission::Run(_)));
+        assert!(matches!(slot.admit(), Admission::Queued));
+        assert!(matches!(slot.admit(), Admission::Queued));
+        // the queued fires are coalesced into a single run
+        assert!(slot.finish().is_some());
+        assert!(slot.finish().is_none());
+    }
+
+    #[test]
+    fn slots_outlive_a_reload() {
+        let slots = RunSlots::default();
+        let slot = slots.slot("daily|rustup", Overlap::Skip);
+        assert!(matches!(slot.admit(), Admission::Run(_)));
+        let reloaded = slots.slot("daily|rustup", Overlap::Skip);
+        assert!(matches!(reloaded.admit(), Admission::Skip));
+        let changed = slots.slot("daily|rustup", Overlap::Queue);
+        assert!(matches!(changed.admit(), Admission::Run(_)));
+        let other = slots.slot("hourly|rustup", Overlap::Skip);
+        assert!(matches!(other.admit(), Admission::Run(_)));
+    }
+
+    #[test]
+    fn replace_stops_previous_run() {
+        let slot = RunSlot::new(Overlap::Replace);
+        let Admission::Run(cancel) = slot.admit() else {
+            panic!("first fire should run");
+        };
+        let slot_c = slot.clone();
+        let replacement = thread::spawn(move || slot_c.admit());
+        // the previous run notices it has been asked to stop, and finishes
+        while !cancel.load(Ordering::SeqCst) {
+            thread::yield_now();
+        }
+        assert!(slot.finish().is_none());
+        match replacement.join() {
+            Ok(Admission::Run(cancel)) => assert!(!cancel.load(Ordering::SeqCst)),
+            _ => panic!("replacement should run"),
+        }
+    }
+}
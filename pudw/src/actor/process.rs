@@ -0,0 +1,266 @@
+// Copyright (c) 2022 pud developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+// job process setup and signalling
+
+use crate::constants::DEFAULT_SHELL;
+#[cfg(unix)]
+use nix::{
+    errno::Errno,
+    libc,
+    sys::{
+        resource::{setrlimit, Resource},
+        signal::{killpg, Signal},
+    },
+    unistd::{Group, Pid, User},
+};
+use pudlib::Command;
+#[cfg(unix)]
+use pudlib::Limits;
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
+use std::{env, io, process::Child};
+#[cfg(windows)]
+use tracing::warn;
+
+/// Build the process for a command, either running the program and arguments
+/// in argv directly or running cmd with a shell
+pub(crate) fn build_command(command: &Command) -> io::Result<std::process::Command> {
+    let mut cmd = if let Some((program, args)) = command.argv().split_first() {
+        let mut cmd = std::process::Command::new(program);
+        _ = cmd.args(args);
+        cmd
+    } else {
+        let shell = command
+            .shell()
+            .clone()
+            .or_else(|| env::var("SHELL").ok())
+            .unwrap_or_else(|| DEFAULT_SHELL.to_string());
+        let mut cmd = std::process::Command::new(shell);
+        _ = cmd.arg("-c");
+        _ = cmd.arg(command.cmd());
+        cmd
+    };
+    if *command.env_clear() {
+        _ = cmd.env_clear();
+    }
+    _ = cmd.envs(command.env());
+    if let Some(cwd) = command.cwd() {
+        _ = cmd.current_dir(cwd);
+    }
+    isolate(&mut cmd, command)?;
+    Ok(cmd)
+}
+
+// Start the command in its own process group, so the whole tree can be
+// signalled, and apply its user, group and resource limits
+#[cfg(unix)]
+fn isolate(cmd: &mut std::process::Command, command: &Command) -> io::Result<()> {
+    let user = command
+        .user()
+        .as_ref()
+        .map(|name| {
+            User::from_name(name)?
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no user '{name}'")))
+        })
+        .transpose()?;
+    let group = command
+        .group()
+        .as_ref()
+        .map(|name| {
+            Group::from_name(name)?.ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, format!("no group '{name}'"))
+            })
+        })
+        .transpose()?;
+
+    if let Some(gid) = group
+        .map(|group| group.gid)
+        .or_else(|| user.as_ref().map(|user| user.gid))
+    {
+        _ = cmd.gid(gid.as_raw());
+    }
+    if let Some(user) = user {
+        _ = cmd.uid(user.uid.as_raw());
+    }
+    _ = cmd.process_group(0);
+
+    let limits = *command.limits();
+    if limits != Limits::default() {
+        // SAFETY: the closure runs in the child between fork and exec, and
+        // only makes async-signal-safe system calls without allocating
+        #[allow(unsafe_code)]
+        unsafe {
+            _ = cmd.pre_exec(move || apply_limits(&limits));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn isolate(_cmd: &mut std::process::Command, command: &Command) -> io::Result<()> {
+    if command.user().is_some()
+        || command.group().is_some()
+        || *command.limits() != pudlib::Limits::default()
+    {
+        warn!("user, group and limits are not supported on this platform");
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn apply_limits(limits: &Limits) -> io::Result<()> {
+    let rlimits = [
+        (Resource::RLIMIT_CPU, limits.cpu_secs()),
+        (Resource::RLIMIT_AS, limits.address_space()),
+        (Resource::RLIMIT_NOFILE, limits.open_files()),
+    ];
+    for (resource, limit) in rlimits {
+        if let Some(limit) = *limit {
+            setrlimit(resource, limit, limit)?;
+        }
+    }
+    if let Some(nice) = *limits.nice() {
+        // SAFETY: setpriority has no memory safety requirements
+        #[allow(unsafe_code)]
+        let res = unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) };
+        if res == -1 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Ask every process in the job to terminate, giving them a chance to clean up
+#[cfg(unix)]
+pub(crate) fn terminate(child: &Child) -> io::Result<()> {
+    signal_group(child, Signal::SIGTERM)
+}
+
+#[cfg(windows)]
+pub(crate) fn terminate(_child: &Child) -> io::Result<()> {
+    // there is no graceful termination, the child is killed after the grace period
+    Ok(())
+}
+
+/// Kill every process in the job
+#[cfg(unix)]
+pub(crate) fn kill_job(child: &mut Child) -> io::Result<()> {
+    signal_group(child, Signal::SIGKILL)
+}
+
+#[cfg(windows)]
+pub(crate) fn kill_job(child: &mut Child) -> io::Result<()> {
+    child.kill()
+}
+
+/// Kill anything left in the job's process group after the child has exited
+#[cfg(unix)]
+pub(crate) fn kill_remaining(child: &Child) -> io::Result<()> {
+    match signal_group(child, Signal::SIGKILL) {
+        // the group is already empty
+        Err(e) if e.raw_os_error() == Some(Errno::ESRCH as i32) => Ok(()),
+        res => res,
+    }
+}
+
+#[cfg(windows)]
+pub(crate) fn kill_remaining(_child: &Child) -> io::Result<()> {
+    // processes aren't grouped, so there is nothing more that can be killed
+    Ok(())
+}
+
+// The child is the leader of the job's process group, so the group id is the
+// child's pid
+#[cfg(unix)]
+fn signal_group(child: &Child, signal: Signal) -> io::Result<()> {
+    let pgid = Pid::from_raw(i32::try_from(child.id()).map_err(|_| Errno::ESRCH)?);
+    killpg(pgid, signal)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::build_command;
+    use anyhow::Result;
+    use pudlib::Command;
+    use std::{ffi::OsStr, path::Path};
+    use toml::from_str;
+    #[cfg(unix)]
+    use {
+        super::kill_job,
+        std::{io::Read, process::Stdio, time::Instant},
+    };
+
+    #[test]
+    fn argv_bypasses_shell() -> Result<()> {
+        let command: Command = from_str(
+            r#"argv = ["cargo", "build"]
+env = { RUST_LOG = "info" }
+cwd = "/tmp"
+"#,
+        )?;
+        let cmd = build_command(&command)?;
+        assert_eq!(cmd.get_program(), "cargo");
+        assert_eq!(cmd.get_args().collect::<Vec<&OsStr>>(), ["build"]);
+        assert_eq!(
+            cmd.get_envs().collect::<Vec<_>>(),
+            [(OsStr::new("RUST_LOG"), Some(OsStr::new("info")))]
+        );
+        assert_eq!(cmd.get_current_dir(), Some(Path::new("/tmp")));
+        Ok(())
+    }
+
+    #[test]
+    fn cmd_uses_shell() -> Result<()> {
+        let command: Command = from_str(
+            r#"cmd = "uname -a"
+shell = "/bin/bash"
+"#,
+        )?;
+        let cmd = build_command(&command)?;
+        assert_eq!(cmd.get_program(), "/bin/bash");
+        assert_eq!(cmd.get_args().collect::<Vec<&OsStr>>(), ["-c", "uname -a"]);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unknown_user_fails() -> Result<()> {
+        let command: Command = from_str(
+            r#"cmd = "uname -a"
+user = "no-such-pud-user"
+"#,
+        )?;
+        assert!(build_command(&command).is_err());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn kill_job_kills_background_processes() -> Result<()> {
+        let command: Command = from_str(
+            r#"cmd = "sleep 30 & sleep 30"
+shell = "/bin/sh"
+"#,
+        )?;
+        let mut cmd = build_command(&command)?;
+        _ = cmd.stdout(Stdio::piped());
+        let mut child = cmd.spawn()?;
+        let started = Instant::now();
+        kill_job(&mut child)?;
+        _ = child.wait()?;
+        // the background sleep would hold stdout open if it survived
+        let mut output = String::new();
+        if let Some(mut stdout) = child.stdout.take() {
+            _ = stdout.read_to_string(&mut output)?;
+        }
+        assert!(started.elapsed().as_secs() < 10);
+        Ok(())
+    }
+}
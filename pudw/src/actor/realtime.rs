@@ -0,0 +1,190 @@
+// Copyright (c) 2022 pud developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+// realtime schedule deadlines
+
+use super::overlap::RunSlot;
+use pudlib::Realtime;
+use std::time::Duration;
+use time::OffsetDateTime;
+use tracing::{debug, warn};
+
+/// The longest the realtime monitor sleeps before checking the clock again.
+/// This bounds the effect of wall clock adjustments made while sleeping.
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+/// The most missed occurrences counted when a tick arrives late
+const MAX_MISSED: usize = 10_000;
+
+/// A realtime schedule loaded on this worker
+#[derive(Clone, Debug)]
+pub(crate) struct RealtimeSchedule {
+    /// A key identifying this schedule across worker restarts
+    pub(crate) key: String,
+    /// The parsed calendar
+    pub(crate) realtime: Realtime,
+    /// Should missed runs be caught up
+    pub(crate) persistent: bool,
+    /// The commands to run
+    pub(crate) cmds: Vec<String>,
+    /// Tracks the current run of the commands
+    pub(crate) slot: RunSlot,
+    /// The next time this schedule should fire
+    next: Option<OffsetDateTime>,
+    /// The occurrence this schedule last fired for
+    last_fired: Option<OffsetDateTime>,
+}
+
+impl RealtimeSchedule {
+    pub(crate) fn new(
+        key: String,
+        realtime: Realtime,
+        persistent: bool,
+        cmds: Vec<String>,
+        slot: RunSlot,
+        now: OffsetDateTime,
+    ) -> Self {
+        let next = realtime.next_after(now);
+        Self {
+            key,
+            realtime,
+            persistent,
+            cmds,
+            slot,
+            next,
+            last_fired: None,
+        }
+    }
+
+    /// Check this schedule against the given time, returning the occurrence to
+    /// fire if one is due.
+    ///
+    /// * An occurrence at or before the last one fired is never fired again,
+    ///   e.g. when the wall clock steps backwards or repeats a leap second.
+    /// * Occurrences missed because the tick arrived late are coalesced into a
+    ///   single run for the latest one.
+    pub(crate) fn poll(&mut self, now: OffsetDateTime) -> Option<OffsetDateTime> {
+        let next = self.next.filter(|next| *next <= now)?;
+
+        if let Some(last) = self.last_fired.filter(|last| next <= *last) {
+            debug!("'{}' already fired for {next}, skipping", self.key);
+            self.next = self.realtime.next_after(last.max(now));
+            return None;
+        }
+
+        let (missed, occurrence) = self
+            .realtime
+            .upcoming(next)
+            .take_while(|later| *later <= now)
+            .take(MAX_MISSED)
+            .fold((0, next), |(missed, _), later| (missed + 1, later));
+        if missed > 0 {
+            warn!(
+                "'{}' missed {missed} run(s) since {next}, running once for {occurrence}",
+                self.key
+            );
+        }
+
+        self.last_fired = Some(occurrence);
+        self.next = self.realtime.next_after(now);
+        Some(occurrence)
+    }
+}
+
+/// How long to sleep before the earliest of the given schedules is due
+pub(crate) fn sleep_until_next(schedules: &[RealtimeSchedule], now: OffsetDateTime) -> Duration {
+    schedules
+        .iter()
+        .filter_map(|schedule| schedule.next)
+        .min()
+        .map_or(MAX_SLEEP, |next| {
+            Duration::try_from(next - now)
+                .unwrap_or(Duration::ZERO)
+                .min(MAX_SLEEP)
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{sleep_until_next, RealtimeSchedule, RunSlot, MAX_SLEEP};
+    use anyhow::Result;
+    use pudlib::{parse_calendar, Overlap};
+    use std::time::Duration;
+    use time::macros::datetime;
+
+    fn schedule(calendar: &str) -> Result<RealtimeSchedule> {
+        let realtime = parse_calendar(calendar)?;
+        Ok(RealtimeSchedule::new(
+            calendar.to_string(),
+            realtime,
+            false,
+            vec![],
+            RunSlot::new(Overlap::Allow),
+            datetime!(2024-02-12 03:59:58 UTC),
+        ))
+    }
+
+    #[test]
+    fn fires_once_per_occurrence() -> Result<()> {
+        let mut schedule = schedule("*-*-* 04:00:00")?;
+        assert_eq!(schedule.poll(datetime!(2024-02-12 03:59:59.999 UTC)), None);
+        assert_eq!(
+            schedule.poll(datetime!(2024-02-12 04:00:00 UTC)),
+            Some(datetime!(2024-02-12 04:00:00 UTC))
+        );
+        // a second tick within the same second does not fire again
+        assert_eq!(schedule.poll(datetime!(2024-02-12 04:00:00.5 UTC)), None);
+        assert_eq!(
+            schedule.poll(datetime!(2024-02-13 04:00:00.001 UTC)),
+            Some(datetime!(2024-02-13 04:00:00 UTC))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn late_tick_coalesces() -> Result<()> {
+        let mut schedule = schedule("*-*-* *:*:00")?;
+        // the monitor was paused for several minutes
+        assert_eq!(
+            schedule.poll(datetime!(2024-02-12 04:03:30 UTC)),
+            Some(datetime!(2024-02-12 04:03:00 UTC))
+        );
+        assert_eq!(schedule.poll(datetime!(2024-02-12 04:03:31 UTC)), None);
+        assert_eq!(
+            schedule.poll(datetime!(2024-02-12 04:04:00 UTC)),
+            Some(datetime!(2024-02-12 04:04:00 UTC))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn clock_step_backwards() -> Result<()> {
+        let mut schedule = schedule("*-*-* 04:00:00")?;
+        assert!(schedule.poll(datetime!(2024-02-12 04:00:00 UTC)).is_some());
+        // the clock is stepped back over the occurrence that already fired
+        schedule.next = Some(datetime!(2024-02-12 04:00:00 UTC));
+        assert_eq!(schedule.poll(datetime!(2024-02-12 04:00:01 UTC)), None);
+        assert_eq!(schedule.next, Some(datetime!(2024-02-13 04:00:00 UTC)));
+        Ok(())
+    }
+
+    #[test]
+    fn sleep_to_earliest() -> Result<()> {
+        let now = datetime!(2024-02-12 03:59:59.25 UTC);
+        let schedules = vec![schedule("*-*-* 05:00:00")?, schedule("*-*-* 04:00:00")?];
+        assert_eq!(
+            sleep_until_next(&schedules, now),
+            Duration::from_millis(750)
+        );
+        let now = datetime!(2024-02-12 03:58:00 UTC);
+        assert_eq!(sleep_until_next(&schedules, now), MAX_SLEEP);
+        let now = datetime!(2024-02-12 04:00:01 UTC);
+        assert_eq!(sleep_until_next(&schedules, now), Duration::ZERO);
+        assert_eq!(sleep_until_next(&[], now), MAX_SLEEP);
+        Ok(())
+    }
+}
@@ -0,0 +1,79 @@
+// Copyright (c) 2022 pud developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+// the jobs running on this worker, so they can be cancelled one at a time
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard, OnceLock},
+};
+use uuid::Uuid;
+
+/// The jobs currently running on this worker, keyed by job id
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RunningJobs {
+    jobs: Arc<Mutex<HashMap<Uuid, Arc<OnceLock<Uuid>>>>>,
+}
+
+impl RunningJobs {
+    /// Track a job that has started.  The returned cell is set to the id of
+    /// the manager that cancelled the job, if it is cancelled.
+    pub(crate) fn register(&self, id: Uuid) -> Arc<OnceLock<Uuid>> {
+        let cancelled_by = Arc::new(OnceLock::new());
+        let _old = self.lock().insert(id, cancelled_by.clone());
+        cancelled_by
+    }
+
+    /// Stop tracking a job that has ended
+    pub(crate) fn remove(&self, id: &Uuid) {
+        let _old = self.lock().remove(id);
+    }
+
+    /// Ask a running job to stop on behalf of the given manager, returning
+    /// false if no such job is running
+    pub(crate) fn cancel(&self, id: &Uuid, manager_id: Uuid) -> bool {
+        self.lock().get(id).is_some_and(|cancelled_by| {
+            // a job that is already being cancelled keeps its first canceller
+            _ = cancelled_by.set(manager_id);
+            true
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<Uuid, Arc<OnceLock<Uuid>>>> {
+        match self.jobs.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RunningJobs;
+    use uuid::Uuid;
+
+    #[test]
+    fn cancel_running_job() {
+        let running = RunningJobs::default();
+        let id = Uuid::new_v4();
+        let manager_id = Uuid::new_v4();
+        let cancelled_by = running.register(id);
+        assert!(cancelled_by.get().is_none());
+        assert!(running.cancel(&id, manager_id));
+        assert_eq!(cancelled_by.get(), Some(&manager_id));
+    }
+
+    #[test]
+    fn cancel_unknown_job() {
+        let running = RunningJobs::default();
+        let id = Uuid::new_v4();
+        let _cancelled_by = running.register(id);
+        running.remove(&id);
+        assert!(!running.cancel(&id, Uuid::new_v4()));
+    }
+}
@@ -8,5 +8,17 @@
 
 //! Constants
 
+use std::time::Duration;
+
+/// How long a timed out command has to exit after it is asked to terminate
+pub(crate) const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(10);
+/// How long the output of a job may stay open after the job exits before
+/// anything left in its process group is killed
+pub(crate) const OUTPUT_GRACE_PERIOD: Duration = Duration::from_secs(5);
+/// The longest line of job output sent in one piece, longer lines are chunked
+pub(crate) const MAX_LINE_BYTES: usize = 16 * 1024;
+/// The shell used to run commands when neither the command nor `$SHELL` name one
+pub(crate) const DEFAULT_SHELL: &str = "/bin/sh";
+
 #[cfg(test)]
 pub(crate) const TEST_PATH: &str = "test/config.toml";
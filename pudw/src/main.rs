@@ -250,6 +250,7 @@ mod constants;
 mod error;
 mod model;
 mod runtime;
+mod state;
 
 fn main() {
     process::exit(runtime::run::<Vec<&str>, &str>(None).map_or_else(clap_or_error, success))
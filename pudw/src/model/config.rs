@@ -11,7 +11,7 @@
 use getset::{Getters, Setters};
 use pudlib::{LogConfig, Verbosity};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::{env, path::PathBuf};
 use tracing::Level;
 
 /// The configuration
@@ -34,6 +34,8 @@ pub(crate) struct Config {
     name: String,
     level: Option<Level>,
     with_level: bool,
+    state_dir: PathBuf,
+    timezone: Option<String>,
 }
 
 impl Config {
@@ -107,6 +109,11 @@ impl From<TomlConfig> for Config {
         let server_addr = config.actix().ip().clone();
         let server_port = *config.actix().port();
         let retry_count = *config.retry_count();
+        let timezone = config.timezone().clone();
+        let state_dir = config
+            .state_dir()
+            .as_ref()
+            .map_or_else(|| default_state_dir(&name), PathBuf::from);
         let (target, thread_id, thread_names, line_numbers, with_level) =
             if let Some(tracing) = config.tracing() {
                 (
@@ -133,10 +140,19 @@ impl From<TomlConfig> for Config {
             name,
             level: None,
             with_level,
+            state_dir,
+            timezone,
         }
     }
 }
 
+fn default_state_dir(name: &str) -> PathBuf {
+    dirs2::data_local_dir()
+        .unwrap_or_else(env::temp_dir)
+        .join("pudw")
+        .join(name)
+}
+
 /// The TOML configuration.
 #[derive(Clone, Debug, Default, Deserialize, Eq, Getters, PartialEq, Serialize)]
 #[getset(get = "pub(crate)")]
@@ -149,6 +165,10 @@ pub(crate) struct TomlConfig {
     retry_count: usize,
     /// The name of this worker
     name: String,
+    /// The directory used to store local worker state
+    state_dir: Option<String>,
+    /// The default timezone realtime schedules are evaluated in
+    timezone: Option<String>,
 }
 
 /// actix client configuration
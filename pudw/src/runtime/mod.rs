@@ -9,24 +9,26 @@
 // Runtime
 
 use crate::{
-    actor::Worker,
+    actor::{RunSlots, RunningJobs, Worker},
     model::config::{Config, TomlConfig},
+    state::{EventSender, Spool, Timestamps},
 };
 use actix::{io::SinkWrite, spawn, Actor, StreamHandler, System};
 use anyhow::{Context, Result};
 use awc::{http::Version, Client};
 use clap::Parser;
 use futures::StreamExt;
-use pudlib::{header, initialize, load, Cli, PudxBinary};
+use pudlib::{header, initialize, load, validate_timezone, Cli, PudxBinary};
 #[cfg(unix)]
 use rustls::crypto::aws_lc_rs;
 use std::{
     ffi::OsString,
     io::{self, Write},
+    sync::{atomic::AtomicBool, Arc, Condvar},
     thread::sleep,
     time::Duration,
 };
-use tokio::sync::mpsc::unbounded_channel;
+use tokio::sync::{mpsc::unbounded_channel, Mutex};
 use tracing::{debug, error, info};
 
 const HEADER_PREFIX: &str = r"██████╗ ██╗   ██╗██████╗ ██╗    ██╗
@@ -60,6 +62,10 @@ where
     // Setup logging
     initialize(&mut config)?;
 
+    if let Some(timezone) = config.timezone() {
+        validate_timezone(timezone)?;
+    }
+
     // Output the pretty header
     header::<Config, dyn Write>(&config, HEADER_PREFIX, Some(&mut io::stdout()))?;
 
@@ -69,12 +75,35 @@ where
     let url = config.server_url();
     let mut retry_count = *config.retry_count();
     let mut error_count = 0;
+    let rebooted = Arc::new(AtomicBool::new(false));
 
     if !args.dry_run() {
+        let spool = Spool::open(config.state_dir());
+        // The jobs outlive a connection, so their events are sent down one
+        // channel for the life of the worker and picked up by each connection
+        let (tx, rx) = unbounded_channel();
+        let tx = EventSender::new(tx, spool.clone());
+        let rx = Arc::new(Mutex::new(rx));
+        // Jobs keep running through a reconnect, so what tracks them is kept
+        // for the life of the worker too
+        let running_pair = Arc::new((std::sync::Mutex::new(false), Condvar::new()));
+        let running_jobs = RunningJobs::default();
+        let run_slots = RunSlots::default();
+        let timestamps = Timestamps::load(config.state_dir());
+
         while retry_count > 0 {
             let sys = System::new();
             let url_c = url.clone();
-            let (tx, mut rx) = unbounded_channel();
+            let timestamps = timestamps.clone();
+            let timezone = config.timezone().clone();
+            let name = config.name().clone();
+            let rebooted = rebooted.clone();
+            let tx = tx.clone();
+            let rx = rx.clone();
+            let spool = spool.clone();
+            let running_pair = running_pair.clone();
+            let running_jobs = running_jobs.clone();
+            let run_slots = run_slots.clone();
             sys.block_on(async move {
                 let awc = Client::builder()
                     .max_http_version(Version::HTTP_11)
@@ -90,14 +119,28 @@ where
                             _ = Worker::add_stream(stream, ctx);
                             Worker::builder()
                                 .addr(SinkWrite::new(sink, ctx))
-                                .tx(tx.clone())
+                                .tx(tx)
+                                .spool(spool.clone())
+                                .timestamps(timestamps)
+                                .timezone(timezone)
+                                .name(name)
+                                .rebooted(rebooted)
+                                .running_pair(running_pair)
+                                .running_jobs(running_jobs)
+                                .run_slots(run_slots)
                                 .build()
                         });
 
                         let status_addr = addr;
                         let _handle = spawn(async move {
-                            while let Some(status) = rx.recv().await {
-                                status_addr.do_send(status);
+                            let mut rx = rx.lock().await;
+                            // replay what the server hasn't acknowledged before
+                            // anything newer
+                            for event in spool.replay(&mut rx) {
+                                status_addr.do_send(event);
+                            }
+                            while let Some(event) = rx.recv().await {
+                                status_addr.do_send(event);
                             }
                         });
                     }
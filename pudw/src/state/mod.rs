@@ -0,0 +1,117 @@
+// Copyright (c) 2022 pud developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+// worker local state
+
+mod spool;
+
+pub(crate) use self::spool::{EventSender, Spool};
+
+use anyhow::{Context, Result};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+};
+use time::OffsetDateTime;
+use tracing::error;
+
+const TIMESTAMPS_FILE_NAME: &str = "timestamps.toml";
+
+/// The last successful fire time of each persistent realtime schedule, stored
+/// on local disk so it survives worker restarts and reconnects.
+#[derive(Clone, Debug)]
+pub(crate) struct Timestamps {
+    path: PathBuf,
+    // Shared with the threads running the schedules, which record a fire once
+    // its commands have succeeded.  Held while the file is written.
+    stamps: Arc<Mutex<BTreeMap<String, i64>>>,
+}
+
+impl Timestamps {
+    /// Load the timestamps stored in the given state directory.  A missing or
+    /// unreadable file yields an empty set of timestamps.
+    pub(crate) fn load(state_dir: &Path) -> Self {
+        let path = state_dir.join(TIMESTAMPS_FILE_NAME);
+        let stamps = match fs::read_to_string(&path) {
+            Ok(contents) => toml::from_str(&contents).unwrap_or_else(|e| {
+                error!("unable to parse {}: {e}", path.display());
+                BTreeMap::new()
+            }),
+            Err(_) => BTreeMap::new(),
+        };
+        Self {
+            path,
+            stamps: Arc::new(Mutex::new(stamps)),
+        }
+    }
+
+    /// The last time the schedule with the given key fired successfully
+    pub(crate) fn last(&self, key: &str) -> Option<OffsetDateTime> {
+        self.lock()
+            .get(key)
+            .and_then(|ts| OffsetDateTime::from_unix_timestamp(*ts).ok())
+    }
+
+    /// Record that the schedule with the given key fired successfully at the
+    /// given time
+    pub(crate) fn record(&self, key: &str, at: OffsetDateTime) -> Result<()> {
+        let mut stamps = self.lock();
+        let _old = stamps.insert(key.to_string(), at.unix_timestamp());
+        self.write(&stamps)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<String, i64>> {
+        match self.stamps.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn write(&self, stamps: &BTreeMap<String, i64>) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Could not create {}", parent.display()))?;
+        }
+        let contents = toml::to_string(stamps)?;
+        let tmp_path = self.path.with_extension("toml.tmp");
+        let mut file = File::create(&tmp_path)
+            .with_context(|| format!("Could not create {}", tmp_path.display()))?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &self.path)
+            .with_context(|| format!("Could not write {}", self.path.display()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Timestamps;
+    use anyhow::Result;
+    use tempfile::tempdir;
+    use time::macros::datetime;
+
+    #[test]
+    fn round_trip() -> Result<()> {
+        let dir = tempdir()?;
+        let state_dir = dir.path();
+        let stamps = Timestamps::load(state_dir);
+        assert!(stamps.last("daily").is_none());
+        stamps.record("daily", datetime!(2024-02-12 04:00:00 UTC))?;
+
+        let reloaded = Timestamps::load(state_dir);
+        assert_eq!(
+            reloaded.last("daily"),
+            Some(datetime!(2024-02-12 04:00:00 UTC))
+        );
+        Ok(())
+    }
+}
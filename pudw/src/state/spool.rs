@@ -0,0 +1,414 @@
+// Copyright (c) 2022 pud developers
+//
+// Licensed under the Apache License, Version 2.0
+// <LICENSE-APACHE or https://www.apache.org/licenses/LICENSE-2.0> or the MIT
+// license <LICENSE-MIT or https://opensource.org/licenses/MIT>, at your
+// option. All files in the project carrying such notice may not be copied,
+// modified, or distributed except according to those terms.
+
+// job event spool
+
+use anyhow::{anyhow, Context, Result};
+use bincode::{deserialize_from, serialize};
+use pudlib::{KillReason, WorkerClientToWorkerSession};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fs::{self, File, OpenOptions},
+    io::{Cursor, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+};
+use time::OffsetDateTime;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+const SPOOL_DIR_NAME: &str = "spool";
+const EXTENSION: &str = "spool";
+
+type Journals = HashMap<Uuid, File>;
+
+/// A journal of the events of each job, stored on local disk until the server
+/// acknowledges it has stored the job, so they can be replayed when the
+/// connection to the server drops mid-job.
+#[derive(Clone, Debug)]
+pub(crate) struct Spool {
+    path: PathBuf,
+    // The journals of the jobs that haven't ended, kept open while their
+    // events are appended.  Held while any journal is written or removed.
+    journals: Arc<Mutex<Journals>>,
+}
+
+impl Spool {
+    /// Open the spool in the given state directory.  Nothing is running the
+    /// jobs an earlier run of the worker left unfinished, so they are ended
+    /// here as lost.
+    pub(crate) fn open(state_dir: &Path) -> Self {
+        let spool = Self {
+            path: state_dir.join(SPOOL_DIR_NAME),
+            journals: Arc::new(Mutex::new(HashMap::new())),
+        };
+        for (id, events) in spool.journals() {
+            if let Err(e) = spool.end_lost(id, &events) {
+                error!("unable to end lost job {id}: {e}");
+            }
+        }
+        spool
+    }
+
+    /// Append an event to the journal of its job.  Messages that aren't about
+    /// a job are not spooled.
+    pub(crate) fn record(&self, event: &WorkerClientToWorkerSession) -> Result<()> {
+        self.write(&mut self.lock(), event)
+    }
+
+    /// The events to send once connected to the server: the spooled events of
+    /// every job the server hasn't acknowledged, in the order they were
+    /// recorded for each job, then anything queued that isn't about a job.
+    /// Queued job events are already in the spool, so they are dropped rather
+    /// than sent twice.
+    pub(crate) fn replay(
+        &self,
+        rx: &mut UnboundedReceiver<WorkerClientToWorkerSession>,
+    ) -> Vec<WorkerClientToWorkerSession> {
+        let _journals = self.lock();
+        let mut queued = vec![];
+        while let Ok(event) = rx.try_recv() {
+            if job_id(&event).is_none() {
+                queued.push(event);
+            }
+        }
+        let journals = self.read_journals();
+        if !journals.is_empty() {
+            info!("replaying {} unacknowledged job(s)", journals.len());
+        }
+        journals
+            .into_iter()
+            .flat_map(|(_id, events)| events)
+            .chain(queued)
+            .collect()
+    }
+
+    /// The server has stored the job, so its journal can go
+    pub(crate) fn acknowledge(&self, id: &Uuid) -> Result<()> {
+        let mut journals = self.lock();
+        let _file = journals.remove(id);
+        let path = self.journal_path(id);
+        if path.exists() {
+            fs::remove_file(&path)
+                .with_context(|| format!("Could not remove {}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    fn journal_path(&self, id: &Uuid) -> PathBuf {
+        self.path.join(format!("{id}.{EXTENSION}"))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Journals> {
+        match self.journals.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    // Append an event to the journal of its job, which is opened by the
+    // first event of the job and closed by the last
+    fn write(&self, journals: &mut Journals, event: &WorkerClientToWorkerSession) -> Result<()> {
+        let Some(id) = job_id(event) else {
+            return Ok(());
+        };
+        let record = serialize(event)?;
+        let file = match journals.entry(id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                fs::create_dir_all(&self.path)
+                    .with_context(|| format!("Could not create {}", self.path.display()))?;
+                let path = self.journal_path(&id);
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&path)
+                    .with_context(|| format!("Could not open {}", path.display()))?;
+                entry.insert(file)
+            }
+        };
+        let written = file.write_all(&record);
+        if is_last(event) {
+            let _file = journals.remove(&id);
+        }
+        Ok(written?)
+    }
+
+    // Every journal in the spool, with the events that could be read from it
+    fn journals(&self) -> Vec<(Uuid, Vec<WorkerClientToWorkerSession>)> {
+        let _journals = self.lock();
+        self.read_journals()
+    }
+
+    // As above, for a caller already holding the lock
+    fn read_journals(&self) -> Vec<(Uuid, Vec<WorkerClientToWorkerSession>)> {
+        let Ok(entries) = fs::read_dir(&self.path) else {
+            return vec![];
+        };
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == EXTENSION))
+            .collect();
+        paths.sort();
+
+        let mut journals = vec![];
+        for path in paths {
+            let id = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| Uuid::parse_str(stem).ok());
+            match (id, fs::read(&path)) {
+                (Some(id), Ok(bytes)) => journals.push((id, read_events(&path, &bytes))),
+                (None, _) => warn!("ignoring {}, it is not a job journal", path.display()),
+                (_, Err(e)) => error!("unable to read {}: {e}", path.display()),
+            }
+        }
+        journals
+    }
+
+    // End a job left unfinished by an earlier run of the worker
+    fn end_lost(&self, id: Uuid, events: &[WorkerClientToWorkerSession]) -> Result<()> {
+        let ended = events.iter().any(is_last);
+        let name = events.iter().find_map(|event| match event {
+            WorkerClientToWorkerSession::JobStart { name, .. } => Some(name.clone()),
+            _ => None,
+        });
+        match name {
+            Some(name) if !ended => {
+                warn!("job '{name}' was running when the worker stopped, ending it as lost");
+                let end_time = fs::metadata(self.journal_path(&id))
+                    .and_then(|metadata| metadata.modified())
+                    .map_or_else(|_| OffsetDateTime::now_utc(), OffsetDateTime::from);
+                self.record(&WorkerClientToWorkerSession::Killed {
+                    id,
+                    reason: KillReason::Lost,
+                })?;
+                self.record(&WorkerClientToWorkerSession::JobEnd { id, name, end_time })
+            }
+            // without a start the server has nothing to store, so nothing would
+            // ever acknowledge the journal
+            None if !ended => self.acknowledge(&id),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Sends job events on to the server, recording each in the spool first so
+/// none is lost if the worker stops before the server has it
+#[derive(Clone, Debug)]
+pub(crate) struct EventSender {
+    tx: UnboundedSender<WorkerClientToWorkerSession>,
+    spool: Spool,
+}
+
+impl EventSender {
+    pub(crate) fn new(tx: UnboundedSender<WorkerClientToWorkerSession>, spool: Spool) -> Self {
+        Self { tx, spool }
+    }
+
+    /// Spool the event if it is about a job, and queue it for the server.
+    /// The spool stays locked until the event is queued, so a replay sees
+    /// it in both places or neither.
+    pub(crate) fn send(&self, event: WorkerClientToWorkerSession) -> Result<()> {
+        let mut journals = self.spool.lock();
+        if let Err(e) = self.spool.write(&mut journals, &event) {
+            error!("unable to spool job event: {e}");
+        }
+        self.tx.send(event).map_err(|e| anyhow!("{e}"))
+    }
+}
+
+// The events in a journal.  A write cut short by a crash leaves a partial
+// event at the end, which is dropped.
+fn read_events(path: &Path, bytes: &[u8]) -> Vec<WorkerClientToWorkerSession> {
+    let len = bytes.len() as u64;
+    let mut cursor = Cursor::new(bytes);
+    let mut events = vec![];
+    while cursor.position() < len {
+        match deserialize_from(&mut cursor) {
+            Ok(event) => events.push(event),
+            Err(e) => {
+                warn!("{} ends with a partial event: {e}", path.display());
+                break;
+            }
+        }
+    }
+    events
+}
+
+// Is this the last event of its job
+fn is_last(event: &WorkerClientToWorkerSession) -> bool {
+    matches!(
+        event,
+        WorkerClientToWorkerSession::JobEnd { .. } | WorkerClientToWorkerSession::Skipped { .. }
+    )
+}
+
+// The job an event is about, if it is about one
+fn job_id(event: &WorkerClientToWorkerSession) -> Option<Uuid> {
+    match event {
+        WorkerClientToWorkerSession::JobStart { id, .. }
+        | WorkerClientToWorkerSession::JobEnd { id, .. }
+        | WorkerClientToWorkerSession::Stdout { id, .. }
+        | WorkerClientToWorkerSession::Stderr { id, .. }
+        | WorkerClientToWorkerSession::Exit { id, .. }
+        | WorkerClientToWorkerSession::Killed { id, .. }
+        | WorkerClientToWorkerSession::Cancelled { id, .. }
+        | WorkerClientToWorkerSession::Skipped { id, .. } => Some(*id),
+        WorkerClientToWorkerSession::Text(_)
+        | WorkerClientToWorkerSession::CancelResult { .. }
+        | WorkerClientToWorkerSession::Initialize
+        | WorkerClientToWorkerSession::Schedules { .. } => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{EventSender, Spool};
+    use anyhow::Result;
+    use pudlib::{KillReason, OutputLimits, WorkerClientToWorkerSession};
+    use std::{fs::OpenOptions, io::Write, time::Duration};
+    use tempfile::tempdir;
+    use time::macros::datetime;
+    use tokio::sync::mpsc::unbounded_channel;
+    use uuid::Uuid;
+
+    // The spooled events, with nothing queued
+    fn spooled(spool: &Spool) -> Vec<WorkerClientToWorkerSession> {
+        let (_tx, mut rx) = unbounded_channel();
+        spool.replay(&mut rx)
+    }
+
+    fn start(id: Uuid) -> WorkerClientToWorkerSession {
+        WorkerClientToWorkerSession::JobStart {
+            id,
+            name: "rustup".to_string(),
+            requested_by: None,
+            output: OutputLimits::default(),
+            start_time: datetime!(2026-10-16 04:00:00 UTC),
+        }
+    }
+
+    fn stdout(id: Uuid, line: &str) -> WorkerClientToWorkerSession {
+        WorkerClientToWorkerSession::Stdout {
+            id,
+            offset: Duration::from_millis(5),
+            line: line.to_string(),
+            base64: false,
+        }
+    }
+
+    fn end(id: Uuid) -> WorkerClientToWorkerSession {
+        WorkerClientToWorkerSession::JobEnd {
+            id,
+            name: "rustup".to_string(),
+            end_time: datetime!(2026-10-16 04:01:00 UTC),
+        }
+    }
+
+    #[test]
+    fn replays_until_acknowledged() -> Result<()> {
+        let dir = tempdir()?;
+        let state_dir = dir.path();
+        let spool = Spool::open(state_dir);
+        assert!(spooled(&spool).is_empty());
+
+        let id = Uuid::new_v4();
+        spool.record(&start(id))?;
+        spool.record(&WorkerClientToWorkerSession::Text("hello".to_string()))?;
+        spool.record(&stdout(id, "one"))?;
+        spool.record(&end(id))?;
+
+        let pending = spooled(&spool);
+        assert_eq!(pending.len(), 3);
+        assert!(matches!(
+            &pending[1],
+            WorkerClientToWorkerSession::Stdout { line, .. } if line == "one"
+        ));
+        assert!(matches!(
+            &pending[2],
+            WorkerClientToWorkerSession::JobEnd { .. }
+        ));
+
+        spool.acknowledge(&id)?;
+        assert!(spooled(&spool).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn drops_a_partial_event() -> Result<()> {
+        let dir = tempdir()?;
+        let state_dir = dir.path();
+        let spool = Spool::open(state_dir);
+        let id = Uuid::new_v4();
+        spool.record(&start(id))?;
+        spool.record(&stdout(id, "one"))?;
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(state_dir.join("spool").join(format!("{id}.spool")))?;
+        file.write_all(&[2, 0, 0])?;
+
+        assert_eq!(spooled(&spool).len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn reopening_ends_unfinished_jobs() -> Result<()> {
+        let dir = tempdir()?;
+        let state_dir = dir.path();
+        let spool = Spool::open(state_dir);
+        let (running, ended, headless) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        spool.record(&start(running))?;
+        spool.record(&stdout(running, "one"))?;
+        spool.record(&start(ended))?;
+        spool.record(&end(ended))?;
+        spool.record(&stdout(headless, "orphan"))?;
+
+        let pending = spooled(&Spool::open(state_dir));
+        assert_eq!(pending.len(), 6);
+        assert!(pending.iter().any(|event| matches!(
+            event,
+            WorkerClientToWorkerSession::Killed { id, reason: KillReason::Lost } if *id == running
+        )));
+        assert!(pending.iter().any(|event| matches!(
+            event,
+            WorkerClientToWorkerSession::JobEnd { id, .. } if *id == running
+        )));
+        assert!(!pending.iter().any(|event| matches!(
+            event,
+            WorkerClientToWorkerSession::Stdout { id, .. } if *id == headless
+        )));
+        Ok(())
+    }
+
+    #[test]
+    fn replay_skips_queued_job_events() -> Result<()> {
+        let dir = tempdir()?;
+        let spool = Spool::open(dir.path());
+        let (tx, mut rx) = unbounded_channel();
+        let sender = EventSender::new(tx, spool.clone());
+        let id = Uuid::new_v4();
+        sender.send(start(id))?;
+        sender.send(stdout(id, "one"))?;
+        sender.send(WorkerClientToWorkerSession::CancelResult {
+            manager_id: Uuid::new_v4(),
+            id,
+            cancelled: true,
+        })?;
+
+        let replayed = spool.replay(&mut rx);
+        assert_eq!(replayed.len(), 3);
+        assert!(matches!(
+            &replayed[2],
+            WorkerClientToWorkerSession::CancelResult { .. }
+        ));
+        assert!(rx.try_recv().is_err());
+        Ok(())
+    }
+}